@@ -0,0 +1,37 @@
+// Support shared by the `sqlx::any` backends in `sqlite::any` and `pg::any`.
+
+use std::sync::{Arc, Once};
+
+use sqlx::{Column, Row};
+use sqlx_core::ext::ustr::UStr;
+use sqlx_core::HashMap;
+
+/// Installs the Spin SQLite and PostgreSQL drivers for `sqlx::AnyConnection`,
+/// so that `AnyConnection::connect("spin-sqlite://default")` and
+/// `AnyConnection::connect("spin-pg://...")` work inside Spin.
+///
+/// This may be called any number of times: only the first call installs the
+/// drivers.  Like `sqlx::any::install_default_drivers`, it panics if some other
+/// drivers have already been installed.  To use other drivers as well, pass
+/// [`crate::sqlite::ANY_DRIVER`] and [`crate::pg::ANY_DRIVER`] to
+/// `sqlx::any::install_drivers` along with them.
+pub fn install_any_drivers() {
+    static ONCE: Once = Once::new();
+
+    ONCE.call_once(|| {
+        sqlx::any::install_drivers(&[crate::sqlite::ANY_DRIVER, crate::pg::ANY_DRIVER])
+            .expect("other sqlx::Any drivers already installed")
+    });
+}
+
+// `AnyRow`s look up columns by name in a map which all rows of a result can
+// share, so build it once per result.
+pub(crate) type ColumnNames = Arc<HashMap<UStr, usize>>;
+
+pub(crate) fn column_names<R: Row>(row: &R) -> ColumnNames {
+    Arc::new(row.columns().iter().map(|c| (UStr::new(c.name()), c.ordinal())).collect())
+}
+
+pub(crate) fn unsupported(what: &str, backend: &str) -> sqlx::Error {
+    sqlx::Error::AnyDriverError(format!("{what} not supported for {backend} through sqlx::Any").into())
+}
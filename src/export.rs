@@ -0,0 +1,334 @@
+//! Writing query results out as CSV or NDJSON, a row at a time, to a
+//! `futures::Sink<Vec<u8>>` such as the body of a Spin HTTP response.
+//!
+//! ```no_run
+//! # async fn report(conn: &spin_sqlx::sqlite::Connection, resp: spin_sdk::http::ResponseOutparam) -> Result<(), sqlx::Error> {
+//! use spin_sdk::http::{Headers, OutgoingResponse};
+//!
+//! let headers = Headers::new();
+//! headers.append(&"content-type".into(), &"text/csv".into()).unwrap();
+//! let og = OutgoingResponse::new(headers);
+//! let body = og.take_body();
+//! resp.set(og);
+//!
+//! spin_sqlx::export::csv(sqlx::query("SELECT * FROM orders ORDER BY id"), conn, body).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! In both formats, NULL is distinguished from empty text (an empty CSV field
+//! versus `""`, and `null` versus `""` in JSON), and blobs are written as base64.
+
+use std::fmt::Write;
+
+use base64::Engine;
+use futures_util::{SinkExt, TryStreamExt};
+use sqlx::{Column, Row};
+
+use crate::pg::SpinPgValue;
+use crate::sqlite::SpinSqliteValue;
+
+/// The databases whose results can be exported: [`crate::sqlite::Connection`]
+/// and [`crate::pg::Connection`].
+pub trait Exportable: sqlx::Database + sealed::Sealed {}
+
+impl<DB: sqlx::Database + sealed::Sealed> Exportable for DB {}
+
+mod sealed {
+    pub enum Field<'a> {
+        Null,
+        Bool(bool),
+        Int(i64),
+        Uint(u64),
+        // Kept apart from `Float` so it's written as the `f32` it is, not
+        // widened to (say) 0.10000000149011612
+        Float32(f32),
+        Float(f64),
+        Text(&'a str),
+        Blob(&'a [u8]),
+    }
+
+    pub trait Sealed: sqlx::Database {
+        fn value(row: &Self::Row, index: usize) -> Result<Self::ValueRef<'_>, sqlx::Error>;
+        fn field<'v>(value: &'v Self::ValueRef<'_>) -> Result<Field<'v>, sqlx::Error>;
+    }
+}
+
+use sealed::Field;
+
+impl sealed::Sealed for crate::sqlite::Connection {
+    fn value(row: &Self::Row, index: usize) -> Result<SpinSqliteValue, sqlx::Error> {
+        row.try_get_raw(index)
+    }
+
+    fn field<'v>(value: &'v SpinSqliteValue) -> Result<Field<'v>, sqlx::Error> {
+        Ok(match value.as_inner() {
+            spin_sdk::sqlite::Value::Integer(i) => Field::Int(*i),
+            spin_sdk::sqlite::Value::Real(r) => Field::Float(*r),
+            spin_sdk::sqlite::Value::Text(t) => Field::Text(t),
+            spin_sdk::sqlite::Value::Blob(b) => Field::Blob(b),
+            spin_sdk::sqlite::Value::Null => Field::Null,
+        })
+    }
+}
+
+impl sealed::Sealed for crate::pg::Connection {
+    fn value(row: &Self::Row, index: usize) -> Result<SpinPgValue, sqlx::Error> {
+        row.try_get_raw(index)
+    }
+
+    fn field<'v>(value: &'v SpinPgValue) -> Result<Field<'v>, sqlx::Error> {
+        Ok(match value.as_inner() {
+            spin_sdk::pg::DbValue::Boolean(b) => Field::Bool(*b),
+            spin_sdk::pg::DbValue::Int8(i) => Field::Int((*i).into()),
+            spin_sdk::pg::DbValue::Int16(i) => Field::Int((*i).into()),
+            spin_sdk::pg::DbValue::Int32(i) => Field::Int((*i).into()),
+            spin_sdk::pg::DbValue::Int64(i) => Field::Int(*i),
+            spin_sdk::pg::DbValue::Uint8(i) => Field::Uint((*i).into()),
+            spin_sdk::pg::DbValue::Uint16(i) => Field::Uint((*i).into()),
+            spin_sdk::pg::DbValue::Uint32(i) => Field::Uint((*i).into()),
+            spin_sdk::pg::DbValue::Uint64(i) => Field::Uint(*i),
+            spin_sdk::pg::DbValue::Floating32(f) => Field::Float32(*f),
+            spin_sdk::pg::DbValue::Floating64(f) => Field::Float(*f),
+            spin_sdk::pg::DbValue::Str(s) => Field::Text(s),
+            spin_sdk::pg::DbValue::Binary(b) => Field::Blob(b),
+            spin_sdk::pg::DbValue::DbNull => Field::Null,
+            spin_sdk::pg::DbValue::Unsupported => return Err(sqlx::Error::Decode("Spin doesn't support this column's type: select it as text".into())),
+        })
+    }
+}
+
+/// Runs the query and writes its rows to `sink` as CSV, with a header line of
+/// column names.  Fields are quoted where they need to be and lines end with
+/// CRLF, as in RFC 4180.  NULLs, and floats which JSON can't represent
+/// (infinities and NaN, as in [`ndjson`]), are written as empty fields.
+///
+/// The header is written with the first row, since that's where the column
+/// names come from, so a query which returns no rows writes an empty body:
+/// no header line either.
+pub async fn csv<'q, 'e, 'c: 'e, DB, Q, E, S>(query: Q, executor: E, sink: S) -> Result<(), sqlx::Error>
+where
+    'q: 'e,
+    DB: Exportable,
+    Q: 'q + sqlx::Execute<'q, DB>,
+    E: 'e + sqlx::Executor<'c, Database = DB>,
+    S: futures::Sink<Vec<u8>>,
+    S::Error: std::fmt::Debug,
+{
+    let mut rows = executor.fetch(query);
+    let mut sink = std::pin::pin!(sink);
+    let mut header = true;
+
+    while let Some(row) = rows.try_next().await? {
+        let mut lines = String::new();
+        if header {
+            lines.push_str(&csv_header(&row));
+            header = false;
+        }
+        lines.push_str(&csv_line::<DB>(&row)?);
+        sink.send(lines.into_bytes()).await.map_err(sink_error)?;
+    }
+
+    Ok(())
+}
+
+/// Runs the query and writes its rows to `sink` as newline-delimited JSON: one
+/// object per line, keyed by column name.  NULLs, and floats which JSON can't
+/// represent (infinities and NaN), are written as `null`.
+pub async fn ndjson<'q, 'e, 'c: 'e, DB, Q, E, S>(query: Q, executor: E, sink: S) -> Result<(), sqlx::Error>
+where
+    'q: 'e,
+    DB: Exportable,
+    Q: 'q + sqlx::Execute<'q, DB>,
+    E: 'e + sqlx::Executor<'c, Database = DB>,
+    S: futures::Sink<Vec<u8>>,
+    S::Error: std::fmt::Debug,
+{
+    let mut rows = executor.fetch(query);
+    let mut sink = std::pin::pin!(sink);
+
+    while let Some(row) = rows.try_next().await? {
+        sink.send(ndjson_line::<DB>(&row)?.into_bytes()).await.map_err(sink_error)?;
+    }
+
+    Ok(())
+}
+
+fn csv_header<R: Row>(row: &R) -> String {
+    let names = row.columns().iter().map(|c| csv_text(c.name()));
+    let mut line = names.collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    line
+}
+
+fn csv_line<DB: Exportable>(row: &DB::Row) -> Result<String, sqlx::Error> {
+    let mut line = String::new();
+    for index in 0..row.columns().len() {
+        if index > 0 {
+            line.push(',');
+        }
+        let value = DB::value(row, index)?;
+        match DB::field(&value)? {
+            Field::Null => (),
+            Field::Bool(b) => write!(line, "{b}").unwrap(),
+            Field::Int(i) => write!(line, "{i}").unwrap(),
+            Field::Uint(i) => write!(line, "{i}").unwrap(),
+            Field::Float32(f) if f.is_finite() => write!(line, "{f}").unwrap(),
+            Field::Float(f) if f.is_finite() => write!(line, "{f}").unwrap(),
+            Field::Float32(_) | Field::Float(_) => (),
+            Field::Text(t) => line.push_str(&csv_text(t)),
+            Field::Blob(b) => line.push_str(&base64(b)),
+        }
+    }
+    line.push_str("\r\n");
+    Ok(line)
+}
+
+fn ndjson_line<DB: Exportable>(row: &DB::Row) -> Result<String, sqlx::Error> {
+    let mut line = String::from("{");
+    for (index, column) in row.columns().iter().enumerate() {
+        if index > 0 {
+            line.push(',');
+        }
+        json_text(&mut line, column.name());
+        line.push(':');
+        let value = DB::value(row, index)?;
+        match DB::field(&value)? {
+            Field::Null => line.push_str("null"),
+            Field::Bool(b) => write!(line, "{b}").unwrap(),
+            Field::Int(i) => write!(line, "{i}").unwrap(),
+            Field::Uint(i) => write!(line, "{i}").unwrap(),
+            Field::Float32(f) if f.is_finite() => write!(line, "{f}").unwrap(),
+            Field::Float(f) if f.is_finite() => write!(line, "{f}").unwrap(),
+            Field::Float32(_) | Field::Float(_) => line.push_str("null"),
+            Field::Text(t) => json_text(&mut line, t),
+            Field::Blob(b) => json_text(&mut line, &base64(b)),
+        }
+    }
+    line.push_str("}\n");
+    Ok(line)
+}
+
+fn csv_text(text: &str) -> String {
+    // Empty text is quoted so it doesn't read back as NULL
+    if text.is_empty() || text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn json_text(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn base64(blob: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(blob)
+}
+
+fn sink_error(e: impl std::fmt::Debug) -> sqlx::Error {
+    sqlx::Error::Io(std::io::Error::other(format!("couldn't write export: {e:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_sdk::pg::{DbDataType, DbValue};
+    use spin_sdk::sqlite::Value;
+
+    use super::*;
+    use crate::pg::SpinPgRow;
+    use crate::sqlite::SpinSqliteRow;
+
+    #[test]
+    fn csv_quoting() {
+        let cases = [
+            ("plain", "plain"),
+            ("", "\"\""),
+            ("a,b", "\"a,b\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("two\nlines", "\"two\nlines\""),
+            ("cr\r", "\"cr\r\""),
+            (" spaced ", " spaced "),
+            ("café", "café"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(expected, csv_text(text), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn json_escaping() {
+        let cases = [
+            ("plain", r#""plain""#),
+            ("", r#""""#),
+            ("say \"hi\"", r#""say \"hi\"""#),
+            ("back\\slash", r#""back\\slash""#),
+            ("\n\r\t", r#""\n\r\t""#),
+            // Only control characters below space need escaping
+            ("\u{0}\u{1f}\u{7f}", "\"\\u0000\\u001f\u{7f}\""),
+            ("café ☕", r#""café ☕""#),
+        ];
+        for (text, expected) in cases {
+            let mut out = String::new();
+            json_text(&mut out, text);
+            assert_eq!(expected, out, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn sqlite_rows() {
+        let row = SpinSqliteRow::for_test(vec![
+            ("id", Value::Integer(-1)),
+            ("name, \"full\"", Value::Text("a,b".into())),
+            ("empty", Value::Text("".into())),
+            ("missing", Value::Null),
+            ("score", Value::Real(0.5)),
+            ("nan", Value::Real(f64::NAN)),
+            ("inf", Value::Real(f64::NEG_INFINITY)),
+            ("data", Value::Blob(vec![0, 255])),
+        ]);
+
+        assert_eq!("id,\"name, \"\"full\"\"\",empty,missing,score,nan,inf,data\r\n", csv_header(&row));
+        assert_eq!("-1,\"a,b\",\"\",,0.5,,,AP8=\r\n", csv_line::<crate::sqlite::Connection>(&row).unwrap());
+        assert_eq!(
+            r#"{"id":-1,"name, \"full\"":"a,b","empty":"","missing":null,"score":0.5,"nan":null,"inf":null,"data":"AP8="}"#.to_owned() + "\n",
+            ndjson_line::<crate::sqlite::Connection>(&row).unwrap(),
+        );
+    }
+
+    #[test]
+    fn pg_rows() {
+        let row = SpinPgRow::for_test(vec![
+            ("ok", DbDataType::Boolean, DbValue::Boolean(true)),
+            ("small", DbDataType::Int16, DbValue::Int16(-2)),
+            ("big", DbDataType::Uint64, DbValue::Uint64(u64::MAX)),
+            ("f32", DbDataType::Floating32, DbValue::Floating32(0.1)),
+            ("f64", DbDataType::Floating64, DbValue::Floating64(0.1)),
+            ("nan", DbDataType::Floating32, DbValue::Floating32(f32::NAN)),
+            ("inf", DbDataType::Floating64, DbValue::Floating64(f64::INFINITY)),
+            ("missing", DbDataType::Str, DbValue::DbNull),
+        ]);
+
+        assert_eq!("true,-2,18446744073709551615,0.1,0.1,,,\r\n", csv_line::<crate::pg::Connection>(&row).unwrap());
+        assert_eq!(
+            r#"{"ok":true,"small":-2,"big":18446744073709551615,"f32":0.1,"f64":0.1,"nan":null,"inf":null,"missing":null}"#.to_owned() + "\n",
+            ndjson_line::<crate::pg::Connection>(&row).unwrap(),
+        );
+
+        let unsupported = SpinPgRow::for_test(vec![("x", DbDataType::Other, DbValue::Unsupported)]);
+        assert!(csv_line::<crate::pg::Connection>(&unsupported).is_err());
+        assert!(ndjson_line::<crate::pg::Connection>(&unsupported).is_err());
+    }
+}
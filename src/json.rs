@@ -0,0 +1,188 @@
+// `Serialize` for rows and values, behind the `serde` feature.  Rows become
+// objects keyed by column name; values become their natural JSON types, with
+// blobs as base64 strings.
+
+use base64::Engine;
+use futures_core::stream::BoxStream;
+use futures_util::StreamExt;
+use serde::ser::{Error, SerializeMap};
+use serde::{Serialize, Serializer};
+use sqlx::{Column, Row};
+
+use crate::pg::{SpinPgRow, SpinPgValue};
+use crate::sqlite::{SpinSqliteRow, SpinSqliteValue};
+
+fn serialize_row<R, S>(row: &R, serializer: S) -> Result<S::Ok, S::Error>
+where
+    R: Row,
+    S: Serializer,
+    usize: sqlx::ColumnIndex<R>,
+    for<'r> <R::Database as sqlx::Database>::ValueRef<'r>: Serialize,
+{
+    let mut map = serializer.serialize_map(Some(row.columns().len()))?;
+    for column in row.columns() {
+        let value = row.try_get_raw(column.ordinal()).map_err(S::Error::custom)?;
+        map.serialize_entry(column.name(), &value)?;
+    }
+    map.end()
+}
+
+fn serialize_blob<S: Serializer>(blob: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(blob))
+}
+
+impl Serialize for SpinSqliteRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_row(self, serializer)
+    }
+}
+
+impl Serialize for SpinSqliteValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_inner() {
+            spin_sdk::sqlite::Value::Integer(i) => serializer.serialize_i64(*i),
+            spin_sdk::sqlite::Value::Real(r) => serializer.serialize_f64(*r),
+            spin_sdk::sqlite::Value::Text(t) => serializer.serialize_str(t),
+            spin_sdk::sqlite::Value::Blob(b) => serialize_blob(b, serializer),
+            spin_sdk::sqlite::Value::Null => serializer.serialize_none(),
+        }
+    }
+}
+
+impl Serialize for SpinPgRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_row(self, serializer)
+    }
+}
+
+impl Serialize for SpinPgValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_inner() {
+            spin_sdk::pg::DbValue::Boolean(b) => serializer.serialize_bool(*b),
+            spin_sdk::pg::DbValue::Int8(i) => serializer.serialize_i8(*i),
+            spin_sdk::pg::DbValue::Int16(i) => serializer.serialize_i16(*i),
+            spin_sdk::pg::DbValue::Int32(i) => serializer.serialize_i32(*i),
+            spin_sdk::pg::DbValue::Int64(i) => serializer.serialize_i64(*i),
+            spin_sdk::pg::DbValue::Uint8(i) => serializer.serialize_u8(*i),
+            spin_sdk::pg::DbValue::Uint16(i) => serializer.serialize_u16(*i),
+            spin_sdk::pg::DbValue::Uint32(i) => serializer.serialize_u32(*i),
+            spin_sdk::pg::DbValue::Uint64(i) => serializer.serialize_u64(*i),
+            spin_sdk::pg::DbValue::Floating32(f) => serializer.serialize_f32(*f),
+            spin_sdk::pg::DbValue::Floating64(f) => serializer.serialize_f64(*f),
+            spin_sdk::pg::DbValue::Str(s) => serializer.serialize_str(s),
+            spin_sdk::pg::DbValue::Binary(b) => serialize_blob(b, serializer),
+            spin_sdk::pg::DbValue::DbNull => serializer.serialize_none(),
+            // Rows don't hand these out, but a value could have been made from one
+            spin_sdk::pg::DbValue::Unsupported => Err(S::Error::custom("Spin doesn't support this column's type: select it as text")),
+        }
+    }
+}
+
+/// Adds `fetch_json` to queries, with the `serde` feature.
+pub trait FetchJson<'q, DB: sqlx::Database>: sqlx::Execute<'q, DB> {
+    /// Runs the query, streaming its rows as the text of a JSON array of objects
+    /// keyed by column name, a row at a time, such as to write to an HTTP
+    /// response body:
+    ///
+    /// ```no_run
+    /// # async fn pets(conn: &spin_sqlx::sqlite::Connection) -> Result<(), sqlx::Error> {
+    /// use futures::TryStreamExt;
+    /// use spin_sqlx::FetchJson;
+    ///
+    /// let json = sqlx::query("SELECT name, age FROM pets")
+    ///     .fetch_json(conn)
+    ///     .try_concat()
+    ///     .await?;
+    /// // [{"name":"Fido","age":3},{"name":"Rex","age":5}]
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn fetch_json<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<Vec<u8>, sqlx::Error>>
+    where
+        'q: 'e,
+        Self: 'q,
+        E: 'e + sqlx::Executor<'c, Database = DB>,
+        DB::Row: Serialize,
+    {
+        json_array(executor.fetch(self))
+    }
+}
+
+impl<'q, DB: sqlx::Database, Q: sqlx::Execute<'q, DB>> FetchJson<'q, DB> for Q {}
+
+fn json_array<'e, R: Serialize + 'e>(rows: BoxStream<'e, Result<R, sqlx::Error>>) -> BoxStream<'e, Result<Vec<u8>, sqlx::Error>> {
+    let mut separator = "";
+    let rows = rows.map(move |row| {
+        let mut chunk = std::mem::replace(&mut separator, ",").as_bytes().to_vec();
+        serde_json::to_writer(&mut chunk, &row?).map_err(|e| sqlx::Error::Decode(e.into()))?;
+        Ok(chunk)
+    });
+
+    let open = futures::stream::once(async { Ok(b"[".to_vec()) });
+    let close = futures::stream::once(async { Ok(b"]".to_vec()) });
+    Box::pin(open.chain(rows).chain(close))
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use futures::TryStreamExt;
+    use spin_sdk::pg::{DbDataType, DbValue};
+    use spin_sdk::sqlite::Value;
+
+    use super::*;
+
+    #[test]
+    fn sqlite_rows_are_objects_in_column_order() {
+        let row = SpinSqliteRow::for_test(vec![
+            ("z", Value::Integer(-1)),
+            ("a", Value::Text("say \"hi\"".into())),
+            ("missing", Value::Null),
+            ("score", Value::Real(0.5)),
+            ("whole", Value::Real(2.0)),
+            ("nan", Value::Real(f64::NAN)),
+            ("data", Value::Blob(vec![0, 255])),
+        ]);
+        assert_eq!(
+            r#"{"z":-1,"a":"say \"hi\"","missing":null,"score":0.5,"whole":2.0,"nan":null,"data":"AP8="}"#,
+            serde_json::to_string(&row).unwrap(),
+        );
+    }
+
+    #[test]
+    fn pg_rows_keep_their_json_types() {
+        let row = SpinPgRow::for_test(vec![
+            ("ok", DbDataType::Boolean, DbValue::Boolean(true)),
+            ("small", DbDataType::Int16, DbValue::Int16(-2)),
+            ("big", DbDataType::Uint64, DbValue::Uint64(u64::MAX)),
+            ("f32", DbDataType::Floating32, DbValue::Floating32(0.1)),
+            ("f64", DbDataType::Floating64, DbValue::Floating64(-1.5)),
+            ("text", DbDataType::Str, DbValue::Str("1".into())),
+            ("missing", DbDataType::Int32, DbValue::DbNull),
+            ("data", DbDataType::Binary, DbValue::Binary(b"hi".to_vec())),
+        ]);
+        assert_eq!(
+            r#"{"ok":true,"small":-2,"big":18446744073709551615,"f32":0.1,"f64":-1.5,"text":"1","missing":null,"data":"aGk="}"#,
+            serde_json::to_string(&row).unwrap(),
+        );
+    }
+
+    #[test]
+    fn unsupported_pg_values_are_errors() {
+        let e = serde_json::to_string(&SpinPgValue::from(DbValue::Unsupported)).unwrap_err();
+        assert_eq!("Spin doesn't support this column's type: select it as text", e.to_string());
+    }
+
+    fn fetch_json(rows: Vec<Result<SpinSqliteRow, sqlx::Error>>) -> Result<String, sqlx::Error> {
+        let json = futures::executor::block_on(json_array(Box::pin(futures::stream::iter(rows))).try_concat())?;
+        Ok(String::from_utf8(json).unwrap())
+    }
+
+    #[test]
+    fn rows_are_fetched_as_an_array() {
+        let row = |id| Ok(SpinSqliteRow::for_test(vec![("id", Value::Integer(id))]));
+        assert_eq!("[]", fetch_json(vec![]).unwrap());
+        assert_eq!(r#"[{"id":1}]"#, fetch_json(vec![row(1)]).unwrap());
+        assert_eq!(r#"[{"id":1},{"id":2},{"id":3}]"#, fetch_json(vec![row(1), row(2), row(3)]).unwrap());
+        assert!(matches!(fetch_json(vec![row(1), Err(sqlx::Error::RowNotFound)]), Err(sqlx::Error::RowNotFound)));
+    }
+}
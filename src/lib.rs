@@ -1,2 +1,18 @@
+mod any;
+#[cfg(feature = "serde")]
+mod json;
+mod macros;
+mod percent;
+mod pool;
+
+mod sql;
+
+mod variables;
+
+pub mod export;
 pub mod pg;
 pub mod sqlite;
+
+pub use any::install_any_drivers;
+#[cfg(feature = "serde")]
+pub use json::FetchJson;
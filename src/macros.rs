@@ -0,0 +1,176 @@
+/// Implements `sqlx::Type` and `sqlx::Decode` for a text-backed enum on both
+/// the Spin SQLite and Spin PostgreSQL backends.
+///
+/// `#[derive(sqlx::Type)]` only generates `Type` and `Decode` for the databases
+/// built into sqlx, so it can't do this part for Spin. It does generate an
+/// `Encode` that works with Spin, so keep deriving it (or `sqlx::Encode`) and
+/// use this macro, listing the variants, to fill in the rest.  The text for
+/// each variant is whatever the derived `Encode` writes, so `rename_all` and
+/// `rename` attributes apply as they do in sqlx:
+///
+/// ```
+/// #[derive(Debug, PartialEq, sqlx::Type)]
+/// #[sqlx(type_name = "mood", rename_all = "lowercase")]
+/// enum Mood { Happy, Sad }
+///
+/// spin_sqlx::impl_text_enum!(Mood, "mood", [Mood::Happy, Mood::Sad]);
+/// #
+/// # let value = spin_sqlx::sqlite::SpinSqliteValue::from(spin_sdk::sqlite::Value::Text("sad".into()));
+/// # assert_eq!(Mood::Sad, <Mood as sqlx::Decode<spin_sqlx::sqlite::Connection>>::decode(value).unwrap());
+/// ```
+///
+/// Spin's Postgres interface sees custom types only as text, so cast when
+/// binding and selecting them, e.g. `WHERE mood = $1::text::mood` and
+/// `SELECT mood::text FROM people`.
+#[macro_export]
+macro_rules! impl_text_enum {
+    ($ty:ty, $type_name:literal, [ $($variant:path),+ $(,)? ]) => {
+        impl $ty {
+            #[doc(hidden)]
+            fn __spin_sqlx_from_text(value: &str) -> ::std::result::Result<Self, ::sqlx::error::BoxDynError> {
+                [$($variant),+]
+                    .into_iter()
+                    .find(|variant| variant.__spin_sqlx_to_text() == value)
+                    .ok_or_else(|| format!("invalid value {:?} for enum {}", value, stringify!($ty)).into())
+            }
+
+            // The derived `Encode` is generic over databases, so let it name the variant
+            #[doc(hidden)]
+            fn __spin_sqlx_to_text(&self) -> ::std::string::String {
+                let mut buf = ::std::vec::Vec::new();
+                let _ = <Self as ::sqlx::Encode<$crate::sqlite::Connection>>::encode_by_ref(self, &mut buf);
+                buf.pop()
+                    .map($crate::sqlite::SpinSqliteValue::from)
+                    .and_then(|value| <::std::string::String as ::sqlx::Decode<$crate::sqlite::Connection>>::decode(value).ok())
+                    .expect("derived Encode should write the variant as text")
+            }
+        }
+
+        impl ::sqlx::Type<$crate::pg::Connection> for $ty {
+            fn type_info() -> $crate::pg::SpinPgTypeInfo {
+                $crate::pg::SpinPgTypeInfo::with_name($type_name)
+            }
+            fn compatible(ty: &$crate::pg::SpinPgTypeInfo) -> bool {
+                *ty == $crate::pg::SpinPgTypeInfo::Str || *ty == $crate::pg::SpinPgTypeInfo::with_name($type_name)
+            }
+        }
+        impl<'r> ::sqlx::Decode<'r, $crate::pg::Connection> for $ty {
+            fn decode(value: $crate::pg::SpinPgValue) -> ::std::result::Result<Self, ::sqlx::error::BoxDynError> {
+                let text = <::std::string::String as ::sqlx::Decode<'r, $crate::pg::Connection>>::decode(value)?;
+                Self::__spin_sqlx_from_text(&text)
+            }
+        }
+        impl $crate::pg::PgHasArrayType for $ty {
+            fn array_type_info() -> $crate::pg::SpinPgTypeInfo {
+                $crate::pg::SpinPgTypeInfo::array_of($type_name)
+            }
+        }
+        impl $crate::pg::EncodeArrayElement for $ty {
+            fn encode_element(&self) -> ::std::option::Option<::std::string::String> {
+                ::std::option::Option::Some(self.__spin_sqlx_to_text())
+            }
+        }
+        impl $crate::pg::DecodeArrayElement for $ty {
+            fn decode_element(element: ::std::option::Option<&str>) -> ::std::result::Result<Self, ::sqlx::error::BoxDynError> {
+                match element {
+                    ::std::option::Option::Some(text) => Self::__spin_sqlx_from_text(text),
+                    ::std::option::Option::None => ::std::result::Result::Err(
+                        format!("unexpected NULL element for enum {}", stringify!($ty)).into()
+                    ),
+                }
+            }
+        }
+
+        impl ::sqlx::Type<$crate::sqlite::Connection> for $ty {
+            fn type_info() -> $crate::sqlite::SpinSqliteTypeInfo {
+                $crate::sqlite::SpinSqliteTypeInfo::Text
+            }
+        }
+        impl<'r> ::sqlx::Decode<'r, $crate::sqlite::Connection> for $ty {
+            fn decode(value: $crate::sqlite::SpinSqliteValue) -> ::std::result::Result<Self, ::sqlx::error::BoxDynError> {
+                let text = <::std::string::String as ::sqlx::Decode<'r, $crate::sqlite::Connection>>::decode(value)?;
+                Self::__spin_sqlx_from_text(&text)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Decode, Encode, Type};
+
+    use crate::pg::{self, SpinPgTypeInfo, SpinPgValue};
+    use crate::sqlite::{self, SpinSqliteValue};
+
+    #[derive(Debug, PartialEq, sqlx::Type)]
+    #[sqlx(type_name = "order_status", rename_all = "snake_case")]
+    enum OrderStatus {
+        AwaitingPayment,
+        Shipped,
+        #[sqlx(rename = "gone")]
+        Cancelled,
+    }
+
+    crate::impl_text_enum!(OrderStatus, "order_status", [
+        OrderStatus::AwaitingPayment,
+        OrderStatus::Shipped,
+        OrderStatus::Cancelled,
+    ]);
+
+    const CASES: [(OrderStatus, &str); 3] = [
+        (OrderStatus::AwaitingPayment, "awaiting_payment"),
+        (OrderStatus::Shipped, "shipped"),
+        (OrderStatus::Cancelled, "gone"),
+    ];
+
+    #[test]
+    fn sqlite_round_trip() {
+        for (status, text) in CASES {
+            let mut buf = vec![];
+            let _ = <OrderStatus as Encode<sqlite::Connection>>::encode_by_ref(&status, &mut buf).unwrap();
+            assert!(matches!(&buf[..], [spin_sdk::sqlite::Value::Text(s)] if s == text));
+
+            let value = SpinSqliteValue::from(buf.pop().unwrap());
+            assert_eq!(status, <OrderStatus as Decode<sqlite::Connection>>::decode(value).unwrap());
+        }
+    }
+
+    #[test]
+    fn pg_round_trip() {
+        for (status, text) in CASES {
+            let mut buf = vec![];
+            let _ = <OrderStatus as Encode<pg::Connection>>::encode_by_ref(&status, &mut buf).unwrap();
+            assert!(matches!(&buf[..], [spin_sdk::pg::ParameterValue::Str(s)] if s == text));
+
+            let value = SpinPgValue::from(spin_sdk::pg::DbValue::Str(text.into()));
+            assert_eq!(status, <OrderStatus as Decode<pg::Connection>>::decode(value).unwrap());
+        }
+    }
+
+    #[test]
+    fn pg_array_round_trip() {
+        let statuses = vec![OrderStatus::Shipped, OrderStatus::Cancelled];
+        let mut buf = vec![];
+        let _ = <Vec<OrderStatus> as Encode<pg::Connection>>::encode_by_ref(&statuses, &mut buf).unwrap();
+        assert!(matches!(&buf[..], [spin_sdk::pg::ParameterValue::Str(s)] if s == r#"{"shipped","gone"}"#));
+
+        let value = SpinPgValue::from(spin_sdk::pg::DbValue::Str("{awaiting_payment,gone}".into()));
+        let decoded = <Vec<OrderStatus> as Decode<pg::Connection>>::decode(value).unwrap();
+        assert_eq!(vec![OrderStatus::AwaitingPayment, OrderStatus::Cancelled], decoded);
+    }
+
+    #[test]
+    fn unknown_text_is_an_error() {
+        let value = SpinSqliteValue::from(spin_sdk::sqlite::Value::Text("Shipped".into()));
+        assert!(<OrderStatus as Decode<sqlite::Connection>>::decode(value).is_err());
+        let value = SpinPgValue::from(spin_sdk::pg::DbValue::Str("cancelled".into()));
+        assert!(<OrderStatus as Decode<pg::Connection>>::decode(value).is_err());
+    }
+
+    #[test]
+    fn pg_type_is_the_custom_name_and_accepts_text() {
+        assert_eq!(SpinPgTypeInfo::with_name("order_status"), <OrderStatus as Type<pg::Connection>>::type_info());
+        assert!(<OrderStatus as Type<pg::Connection>>::compatible(&SpinPgTypeInfo::Str));
+        assert!(!<OrderStatus as Type<pg::Connection>>::compatible(&SpinPgTypeInfo::Int32));
+    }
+}
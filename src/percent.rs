@@ -0,0 +1,57 @@
+// Percent-encoding for the parts of connection URLs.  Form encoding (as in
+// `url::Url::query_pairs`) isn't right for these, because database URL parsers
+// don't read `+` as a space.
+
+pub(crate) fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+pub(crate) fn decode(s: &str) -> Result<String, sqlx::Error> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next(), iter.next()];
+            let decoded = match hex {
+                [Some(h), Some(l)] => std::str::from_utf8(&[h, l]).ok().and_then(|h| u8::from_str_radix(h, 16).ok()),
+                _ => None,
+            };
+            bytes.push(decoded.ok_or_else(|| config_error(format!("invalid percent-encoding in '{s}'")))?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| config_error(format!("'{s}' is not valid UTF-8")))
+}
+
+fn config_error(message: String) -> sqlx::Error {
+    sqlx::Error::Configuration(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoding() {
+        assert_eq!("Ab c+", decode("%41b%20c+").unwrap());
+        assert_eq!("é", decode("%C3%A9").unwrap());
+        for bad in ["%", "%4", "%zz", "%FF"] {
+            assert!(decode(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!("a-b.c_d~e", encode("a-b.c_d~e"));
+        assert_eq!("%20%2B%2F%3D%26%25%C3%A9", encode(" +/=&%é"));
+        assert_eq!(" +/=&%é", decode(&encode(" +/=&%é")).unwrap());
+    }
+}
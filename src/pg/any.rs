@@ -0,0 +1,277 @@
+use futures_core::future::BoxFuture;
+use futures_core::stream::BoxStream;
+use futures_util::StreamExt;
+use sqlx::{Column, ConnectOptions, Either, Executor};
+use sqlx_core::any::driver::AnyDriver;
+use sqlx_core::any::{
+    Any, AnyArguments, AnyColumn, AnyConnectOptions, AnyConnectionBackend, AnyQueryResult, AnyRow,
+    AnyStatement, AnyTypeInfo, AnyTypeInfoKind, AnyValueKind,
+};
+
+use crate::any::{column_names, unsupported, ColumnNames};
+
+use super::{Connection, ConnectionOptions, SpinPgArgs, SpinPgColumn, SpinPgRow, SpinPgTypeInfo};
+
+/// The `sqlx::any` driver for `spin-pg:` URLs.  See [`crate::install_any_drivers`].
+///
+/// Spin creates no Postgres databases, so `sqlx::Any::create_database` and
+/// friends are not supported, but migrations are.
+pub const ANY_DRIVER: AnyDriver = AnyDriver::without_migrate::<Connection>();
+
+impl AnyConnectionBackend for Connection {
+    fn name(&self) -> &str {
+        <Connection as sqlx::Database>::NAME
+    }
+
+    fn close(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        sqlx::Connection::close(*self)
+    }
+
+    fn close_hard(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        sqlx::Connection::close_hard(*self)
+    }
+
+    fn ping(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        sqlx::Connection::ping(self)
+    }
+
+    fn begin(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        <Connection as sqlx::TransactionManager>::begin(self)
+    }
+
+    fn commit(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        <Connection as sqlx::TransactionManager>::commit(self)
+    }
+
+    fn rollback(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        <Connection as sqlx::TransactionManager>::rollback(self)
+    }
+
+    fn start_rollback(&mut self) {
+        <Connection as sqlx::TransactionManager>::start_rollback(self)
+    }
+
+    fn shrink_buffers(&mut self) {
+    }
+
+    fn flush(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        sqlx::Connection::flush(self)
+    }
+
+    fn should_flush(&self) -> bool {
+        sqlx::Connection::should_flush(self)
+    }
+
+    fn as_migrate(&mut self) -> Result<&mut (dyn sqlx::migrate::Migrate + Send + 'static), sqlx::Error> {
+        Ok(self)
+    }
+
+    fn fetch_many<'q>(
+        &'q mut self,
+        query: &'q str,
+        _persistent: bool,
+        arguments: Option<AnyArguments<'q>>,
+    ) -> BoxStream<'q, Result<Either<AnyQueryResult, AnyRow>, sqlx::Error>> {
+        let args = match arguments.map(map_arguments).transpose() {
+            Ok(args) => args.unwrap_or_default(),
+            Err(e) => return Box::pin(futures::stream::once(async move { Err(e) })),
+        };
+
+        let mut names = None;
+        Box::pin((&*self).fetch_many(sqlx::query_with(query, args)).map(move |step| match step? {
+            Either::Left(result) => {
+                names = None;  // the next statement's rows may have other columns
+                Ok(Either::Left(AnyQueryResult { rows_affected: result.count(), last_insert_id: None }))
+            },
+            Either::Right(row) => map_row(&row, &mut names).map(Either::Right),
+        }))
+    }
+
+    fn fetch_optional<'q>(
+        &'q mut self,
+        query: &'q str,
+        _persistent: bool,
+        arguments: Option<AnyArguments<'q>>,
+    ) -> BoxFuture<'q, Result<Option<AnyRow>, sqlx::Error>> {
+        Box::pin(async move {
+            let args = arguments.map(map_arguments).transpose()?.unwrap_or_default();
+            let row = (&*self).fetch_optional(sqlx::query_with(query, args)).await?;
+            row.map(|row| map_row(&row, &mut None)).transpose()
+        })
+    }
+
+    fn prepare_with<'c, 'q: 'c>(
+        &'c mut self,
+        _sql: &'q str,
+        _parameters: &[AnyTypeInfo],
+    ) -> BoxFuture<'c, Result<AnyStatement<'q>, sqlx::Error>> {
+        Box::pin(async move { Err(unsupported("prepared statements are", "Spin PostgreSQL")) })
+    }
+
+    fn describe<'q>(&'q mut self, _sql: &'q str) -> BoxFuture<'q, Result<sqlx::Describe<Any>, sqlx::Error>> {
+        Box::pin(async move { Err(unsupported("describe is", "Spin PostgreSQL")) })
+    }
+}
+
+fn map_row(row: &SpinPgRow, names: &mut Option<ColumnNames>) -> Result<AnyRow, sqlx::Error> {
+    let names = names.get_or_insert_with(|| column_names(row));
+    AnyRow::map_from(row, names.clone())
+}
+
+fn map_arguments(args: AnyArguments<'_>) -> Result<SpinPgArgs, sqlx::Error> {
+    let inner = args.values.0.into_iter()
+        .map(|value| match value {
+            AnyValueKind::Null(_) => Ok(spin_sdk::pg::ParameterValue::DbNull),
+            AnyValueKind::Bool(b) => Ok(spin_sdk::pg::ParameterValue::Boolean(b)),
+            AnyValueKind::SmallInt(i) => Ok(spin_sdk::pg::ParameterValue::Int16(i)),
+            AnyValueKind::Integer(i) => Ok(spin_sdk::pg::ParameterValue::Int32(i)),
+            AnyValueKind::BigInt(i) => Ok(spin_sdk::pg::ParameterValue::Int64(i)),
+            AnyValueKind::Real(r) => Ok(spin_sdk::pg::ParameterValue::Floating32(r)),
+            AnyValueKind::Double(d) => Ok(spin_sdk::pg::ParameterValue::Floating64(d)),
+            AnyValueKind::Text(t) => Ok(spin_sdk::pg::ParameterValue::Str(t.into_owned())),
+            AnyValueKind::Blob(b) => Ok(spin_sdk::pg::ParameterValue::Binary(b.into_owned())),
+            other => Err(sqlx::Error::Encode(format!("Spin PostgreSQL can't bind {other:?}").into())),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(SpinPgArgs { inner })
+}
+
+impl<'a> TryFrom<&'a SpinPgTypeInfo> for AnyTypeInfo {
+    type Error = sqlx::Error;
+
+    fn try_from(type_info: &'a SpinPgTypeInfo) -> Result<Self, Self::Error> {
+        let kind = match type_info {
+            SpinPgTypeInfo::Bool => AnyTypeInfoKind::Bool,
+            SpinPgTypeInfo::Int16 => AnyTypeInfoKind::SmallInt,
+            SpinPgTypeInfo::Int32 => AnyTypeInfoKind::Integer,
+            SpinPgTypeInfo::Int64 => AnyTypeInfoKind::BigInt,
+            SpinPgTypeInfo::Floating32 => AnyTypeInfoKind::Real,
+            SpinPgTypeInfo::Floating64 => AnyTypeInfoKind::Double,
+            SpinPgTypeInfo::Str => AnyTypeInfoKind::Text,
+            SpinPgTypeInfo::Binary => AnyTypeInfoKind::Blob,
+            SpinPgTypeInfo::Null => AnyTypeInfoKind::Null,
+            // These all travel as text
+            SpinPgTypeInfo::BoolArray | SpinPgTypeInfo::Int16Array | SpinPgTypeInfo::Int32Array |
+            SpinPgTypeInfo::Int64Array | SpinPgTypeInfo::Floating32Array | SpinPgTypeInfo::Floating64Array |
+            SpinPgTypeInfo::StrArray | SpinPgTypeInfo::Inet | SpinPgTypeInfo::Cidr | SpinPgTypeInfo::MacAddr |
+            SpinPgTypeInfo::Interval | SpinPgTypeInfo::Money | SpinPgTypeInfo::Custom(_) => AnyTypeInfoKind::Text,
+            SpinPgTypeInfo::Unsupported => {
+                return Err(sqlx::Error::AnyDriverError("the Any driver can't map a column type that Spin doesn't support: select it as text".into()));
+            },
+        };
+        Ok(AnyTypeInfo { kind })
+    }
+}
+
+impl<'a> TryFrom<&'a SpinPgColumn> for AnyColumn {
+    type Error = sqlx::Error;
+
+    fn try_from(column: &'a SpinPgColumn) -> Result<Self, Self::Error> {
+        let type_info = AnyTypeInfo::try_from(column.type_info()).map_err(|e| sqlx::Error::ColumnDecode {
+            index: column.name().to_owned(),
+            source: e.into(),
+        })?;
+        Ok(AnyColumn {
+            ordinal: column.ordinal(),
+            name: column.name().to_owned().into(),
+            type_info,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a AnyConnectOptions> for ConnectionOptions {
+    type Error = sqlx::Error;
+
+    fn try_from(options: &'a AnyConnectOptions) -> Result<Self, Self::Error> {
+        ConnectionOptions::from_url(&options.database_url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Arguments, Row};
+    use spin_sdk::pg::{DbDataType, DbValue};
+
+    use super::*;
+
+    #[test]
+    fn arguments_are_mapped_to_pg_parameters() {
+        let mut args = AnyArguments::default();
+        args.add(true).unwrap();
+        args.add(-2i16).unwrap();
+        args.add(3i32).unwrap();
+        args.add(i64::MAX).unwrap();
+        args.add(0.5f32).unwrap();
+        args.add(0.25f64).unwrap();
+        args.add("text").unwrap();
+        args.add(vec![0u8, 255]).unwrap();
+        args.add(Option::<i32>::None).unwrap();
+
+        let values = map_arguments(args).unwrap().inner.iter().map(|value| format!("{value:?}").replace("ParameterValue::", "")).collect::<Vec<_>>();
+        assert_eq!(
+            ["Boolean(true)", "Int16(-2)", "Int32(3)", "Int64(9223372036854775807)", "Floating32(0.5)", "Floating64(0.25)", "Str(\"text\")", "Binary([0, 255])", "DbNull"],
+            values[..],
+        );
+    }
+
+    #[test]
+    fn type_infos_are_mapped_to_any_kinds() {
+        let cases = [
+            (SpinPgTypeInfo::Bool, AnyTypeInfoKind::Bool),
+            (SpinPgTypeInfo::Int16, AnyTypeInfoKind::SmallInt),
+            (SpinPgTypeInfo::Int32, AnyTypeInfoKind::Integer),
+            (SpinPgTypeInfo::Int64, AnyTypeInfoKind::BigInt),
+            (SpinPgTypeInfo::Floating32, AnyTypeInfoKind::Real),
+            (SpinPgTypeInfo::Floating64, AnyTypeInfoKind::Double),
+            (SpinPgTypeInfo::Str, AnyTypeInfoKind::Text),
+            (SpinPgTypeInfo::Binary, AnyTypeInfoKind::Blob),
+            (SpinPgTypeInfo::Null, AnyTypeInfoKind::Null),
+            (SpinPgTypeInfo::Int64Array, AnyTypeInfoKind::Text),
+            (SpinPgTypeInfo::Inet, AnyTypeInfoKind::Text),
+            (SpinPgTypeInfo::Interval, AnyTypeInfoKind::Text),
+            (SpinPgTypeInfo::with_name("mood"), AnyTypeInfoKind::Text),
+        ];
+        for (type_info, kind) in cases {
+            assert_eq!(kind, AnyTypeInfo::try_from(&type_info).unwrap().kind, "{type_info:?}");
+        }
+
+        match AnyTypeInfo::try_from(&SpinPgTypeInfo::Unsupported) {
+            Err(sqlx::Error::AnyDriverError(e)) => assert!(e.to_string().contains("select it as text"), "{e}"),
+            other => panic!("expected a driver error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rows_are_mapped_to_any_rows() {
+        let row = SpinPgRow::for_test(vec![
+            ("ok", DbDataType::Boolean, DbValue::Boolean(true)),
+            ("small", DbDataType::Int16, DbValue::Int16(-2)),
+            ("id", DbDataType::Int64, DbValue::Int64(7)),
+            ("score", DbDataType::Floating64, DbValue::Floating64(1.5)),
+            ("name", DbDataType::Str, DbValue::Str("Fido".into())),
+            ("data", DbDataType::Binary, DbValue::Binary(vec![1, 2])),
+            ("missing", DbDataType::Int32, DbValue::DbNull),
+        ]);
+        let any_row = map_row(&row, &mut None).unwrap();
+
+        assert!(any_row.try_get::<bool, _>("ok").unwrap());
+        assert_eq!(-2, any_row.try_get::<i16, _>("small").unwrap());
+        assert_eq!(7, any_row.try_get::<i64, _>("id").unwrap());
+        assert_eq!(1.5, any_row.try_get::<f64, _>("score").unwrap());
+        assert_eq!("Fido", any_row.try_get::<String, _>(4).unwrap());
+        assert_eq!(vec![1, 2], any_row.try_get::<Vec<u8>, _>("data").unwrap());
+        assert_eq!(None, any_row.try_get::<Option<i32>, _>("missing").unwrap());
+    }
+
+    #[test]
+    fn rows_with_unsupported_columns_are_errors() {
+        let row = SpinPgRow::for_test(vec![
+            ("id", DbDataType::Int64, DbValue::Int64(7)),
+            ("shape", DbDataType::Other, DbValue::Unsupported),
+        ]);
+        match map_row(&row, &mut None) {
+            Err(sqlx::Error::ColumnDecode { index, .. }) => assert_eq!("shape", index),
+            other => panic!("expected a column decode error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+// Spin's Postgres interface has no array values, so arrays travel as their
+// text representation (e.g. `{1,2,3}`).  When binding, cast the parameter
+// from text in the SQL, e.g. `WHERE id = ANY($1::text::bigint[])`; when
+// selecting, cast the column to text, e.g. `SELECT tags::text FROM posts`.
+
+use std::fmt::Display;
+
+use super::{Connection, SpinPgTypeInfo};
+
+#[derive(Debug)]
+struct BadArrayError(String);
+impl std::error::Error for BadArrayError {}
+impl Display for BadArrayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad array value: {}", self.0)
+    }
+}
+
+fn bad_array(message: impl Into<String>) -> sqlx::error::BoxDynError {
+    Box::new(BadArrayError(message.into()))
+}
+
+/// A type which can be the element of a Postgres array.
+pub trait PgHasArrayType {
+    fn array_type_info() -> SpinPgTypeInfo;
+}
+
+/// An array element type which can be written into a Postgres array literal.
+pub trait EncodeArrayElement {
+    /// The text of the element, or `None` for NULL. This should not be quoted:
+    /// quoting and escaping are applied when the literal is assembled.
+    fn encode_element(&self) -> Option<String>;
+}
+
+/// An array element type which can be read out of Postgres array text output.
+pub trait DecodeArrayElement: Sized {
+    /// Decodes an unquoted, unescaped element, or `None` for NULL.
+    fn decode_element(element: Option<&str>) -> Result<Self, sqlx::error::BoxDynError>;
+}
+
+fn encode_array<T: EncodeArrayElement>(elements: &[T]) -> String {
+    let mut literal = String::from("{");
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            literal.push(',');
+        }
+        match element.encode_element() {
+            None => literal.push_str("NULL"),
+            Some(text) => {
+                literal.push('"');
+                for c in text.chars() {
+                    if c == '"' || c == '\\' {
+                        literal.push('\\');
+                    }
+                    literal.push(c);
+                }
+                literal.push('"');
+            }
+        }
+    }
+    literal.push('}');
+    literal
+}
+
+pub(crate) fn parse_array(text: &str) -> Result<Vec<Option<String>>, sqlx::error::BoxDynError> {
+    let mut text = text.trim();
+
+    // Arrays with non-default bounds are prefixed with their dimensions, e.g. `[0:2]={1,2,3}`
+    if text.starts_with('[') {
+        match text.find('=') {
+            Some(pos) => text = &text[pos + 1..],
+            None => return Err(bad_array("unterminated dimension decoration")),
+        }
+    }
+
+    let inner = text.strip_prefix('{').and_then(|t| t.strip_suffix('}'))
+        .ok_or_else(|| bad_array(format!("expected '{{...}}' but got '{text}'")))?;
+
+    let mut elements = vec![];
+    if inner.trim().is_empty() {
+        return Ok(elements);
+    }
+
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut element = String::new();
+        let mut quoted = false;
+        let mut escaped = false;
+
+        if chars.next_if_eq(&'"').is_some() {
+            quoted = true;
+            loop {
+                match chars.next() {
+                    None => return Err(bad_array("unterminated quoted element")),
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        None => return Err(bad_array("unterminated escape")),
+                        Some(c) => element.push(c),
+                    },
+                    Some(c) => element.push(c),
+                }
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                match c {
+                    '{' | '}' => return Err(bad_array("multi-dimensional arrays are not supported")),
+                    '\\' => {
+                        escaped = true;
+                        match chars.next() {
+                            None => return Err(bad_array("unterminated escape")),
+                            Some(c) => element.push(c),
+                        }
+                    },
+                    c => element.push(c),
+                }
+            }
+            element.truncate(element.trim_end().len());
+        }
+
+        if !quoted && !escaped && element.eq_ignore_ascii_case("NULL") {
+            elements.push(None);
+        } else {
+            elements.push(Some(element));
+        }
+
+        match chars.next() {
+            None => break,
+            Some(',') => continue,
+            Some(c) => return Err(bad_array(format!("unexpected '{c}' after element"))),
+        }
+    }
+
+    Ok(elements)
+}
+
+fn decode_array<T: DecodeArrayElement>(text: &str) -> Result<Vec<T>, sqlx::error::BoxDynError> {
+    parse_array(text)?
+        .iter()
+        .map(|e| T::decode_element(e.as_deref()))
+        .collect()
+}
+
+fn parse_element<T: std::str::FromStr>(element: Option<&str>) -> Result<T, sqlx::error::BoxDynError>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match element {
+        Some(text) => Ok(text.parse()?),
+        None => Err(bad_array("unexpected NULL element (use Option<T> for nullable elements)")),
+    }
+}
+
+impl<'q, T: EncodeArrayElement + PgHasArrayType> sqlx::Encode<'q, Connection> for Vec<T> {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(encode_array(self)));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+impl<'q, T: EncodeArrayElement + PgHasArrayType> sqlx::Encode<'q, Connection> for &[T] {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(encode_array(self)));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+impl<'r, T: DecodeArrayElement + PgHasArrayType> sqlx::Decode<'r, Connection> for Vec<T> {
+    fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        match value.inner {
+            spin_sdk::pg::DbValue::Str(s) => decode_array(&s),
+            _ => Err(bad_array("arrays must be selected as text, e.g. `SELECT col::text`")),
+        }
+    }
+}
+impl<T: PgHasArrayType> sqlx::Type<Connection> for Vec<T> {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        T::array_type_info()
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        *ty == SpinPgTypeInfo::Str || *ty == T::array_type_info()
+    }
+}
+impl<T: PgHasArrayType> sqlx::Type<Connection> for &[T] {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        T::array_type_info()
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        *ty == SpinPgTypeInfo::Str || *ty == T::array_type_info()
+    }
+}
+
+impl<T: PgHasArrayType> PgHasArrayType for Option<T> {
+    fn array_type_info() -> SpinPgTypeInfo {
+        T::array_type_info()
+    }
+}
+impl<T: EncodeArrayElement> EncodeArrayElement for Option<T> {
+    fn encode_element(&self) -> Option<String> {
+        self.as_ref().and_then(|v| v.encode_element())
+    }
+}
+impl<T: DecodeArrayElement> DecodeArrayElement for Option<T> {
+    fn decode_element(element: Option<&str>) -> Result<Self, sqlx::error::BoxDynError> {
+        match element {
+            None => Ok(None),
+            e => T::decode_element(e).map(Some),
+        }
+    }
+}
+
+impl PgHasArrayType for bool {
+    fn array_type_info() -> SpinPgTypeInfo {
+        SpinPgTypeInfo::BoolArray
+    }
+}
+impl EncodeArrayElement for bool {
+    fn encode_element(&self) -> Option<String> {
+        Some(if *self { "t" } else { "f" }.to_owned())
+    }
+}
+impl DecodeArrayElement for bool {
+    fn decode_element(element: Option<&str>) -> Result<Self, sqlx::error::BoxDynError> {
+        match element {
+            Some("t") | Some("true") => Ok(true),
+            Some("f") | Some("false") => Ok(false),
+            Some(other) => Err(bad_array(format!("'{other}' is not a boolean"))),
+            None => parse_element(None),
+        }
+    }
+}
+
+macro_rules! array_element_from_str {
+    ($t:ty, $array_type_info:expr) => {
+        impl PgHasArrayType for $t {
+            fn array_type_info() -> SpinPgTypeInfo {
+                $array_type_info
+            }
+        }
+        impl EncodeArrayElement for $t {
+            fn encode_element(&self) -> Option<String> {
+                Some(self.to_string())
+            }
+        }
+        impl DecodeArrayElement for $t {
+            fn decode_element(element: Option<&str>) -> Result<Self, sqlx::error::BoxDynError> {
+                parse_element(element)
+            }
+        }
+    };
+}
+
+array_element_from_str!(i16, SpinPgTypeInfo::Int16Array);
+array_element_from_str!(i32, SpinPgTypeInfo::Int32Array);
+array_element_from_str!(i64, SpinPgTypeInfo::Int64Array);
+array_element_from_str!(String, SpinPgTypeInfo::StrArray);
+
+// Rust's float formatting doesn't produce the spellings Postgres uses for the
+// special values, but parsing accepts Postgres' spellings.
+macro_rules! array_element_float {
+    ($t:ty, $array_type_info:expr) => {
+        impl PgHasArrayType for $t {
+            fn array_type_info() -> SpinPgTypeInfo {
+                $array_type_info
+            }
+        }
+        impl EncodeArrayElement for $t {
+            fn encode_element(&self) -> Option<String> {
+                let text = if self.is_nan() {
+                    "NaN".to_owned()
+                } else if self.is_infinite() {
+                    if self.is_sign_positive() { "Infinity" } else { "-Infinity" }.to_owned()
+                } else {
+                    self.to_string()
+                };
+                Some(text)
+            }
+        }
+        impl DecodeArrayElement for $t {
+            fn decode_element(element: Option<&str>) -> Result<Self, sqlx::error::BoxDynError> {
+                parse_element(element)
+            }
+        }
+    };
+}
+
+array_element_float!(f32, SpinPgTypeInfo::Floating32Array);
+array_element_float!(f64, SpinPgTypeInfo::Floating64Array);
+
+impl PgHasArrayType for &str {
+    fn array_type_info() -> SpinPgTypeInfo {
+        SpinPgTypeInfo::StrArray
+    }
+}
+impl EncodeArrayElement for &str {
+    fn encode_element(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_owned())
+    }
+
+    #[test]
+    fn parse_plain_elements() {
+        assert_eq!(vec![some("1"), some("2"), some("3")], parse_array("{1,2,3}").unwrap());
+        assert_eq!(vec![some("a b"), some("c")], parse_array("{ a b , c }").unwrap());
+    }
+
+    #[test]
+    fn parse_empty_arrays() {
+        assert!(parse_array("{}").unwrap().is_empty());
+        assert!(parse_array(" { } ").unwrap().is_empty());
+        assert_eq!(vec![some("")], parse_array(r#"{""}"#).unwrap());
+    }
+
+    #[test]
+    fn parse_quoted_elements() {
+        assert_eq!(
+            vec![some("a,b"), some("{x}"), some(" padded ")],
+            parse_array(r#"{"a,b","{x}"," padded "}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_null_versus_quoted_null() {
+        assert_eq!(
+            vec![None, some("NULL"), None, some("NULL"), some("nullable")],
+            parse_array(r#"{NULL,"NULL",null,\NULL,nullable}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_backslash_escapes() {
+        assert_eq!(
+            vec![some(r#"say "hi""#), some(r"back\slash"), some("a,b")],
+            parse_array(r#"{"say \"hi\"","back\\slash",a\,b}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_dimension_decoration() {
+        assert_eq!(vec![some("1"), some("2"), some("3")], parse_array("[1:3]={1,2,3}").unwrap());
+        assert_eq!(vec![some("7")], parse_array("[0:0]={7}").unwrap());
+        assert!(parse_array("[1:3]{1,2,3}").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_malformed_arrays() {
+        assert!(parse_array("1,2,3").is_err());
+        assert!(parse_array(r#"{"unterminated}"#).is_err());
+        assert!(parse_array(r#"{"a"b}"#).is_err());
+        assert!(parse_array("{trailing\\}").is_err());
+        assert!(parse_array("{{1,2},{3,4}}").is_err());
+    }
+
+    #[test]
+    fn encode_quotes_and_escapes_every_element() {
+        assert_eq!("{}", encode_array::<i32>(&[]));
+        assert_eq!(r#"{"1","2"}"#, encode_array(&[1, 2]));
+        assert_eq!(
+            r#"{"a,b","say \"hi\"","back\\slash","NULL",NULL}"#,
+            encode_array(&[Some("a,b"), Some(r#"say "hi""#), Some(r"back\slash"), Some("NULL"), None])
+        );
+        assert_eq!(r#"{"t","f"}"#, encode_array(&[true, false]));
+        assert_eq!(r#"{"NaN","Infinity","-Infinity","1.5"}"#, encode_array(&[f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5]));
+    }
+
+    #[test]
+    fn encode_then_parse_round_trips() {
+        let strings = vec![Some("plain"), Some(""), Some("NULL"), None, Some(r#"{"x": "\y"}"#), Some(" spaced ")];
+        let parsed = parse_array(&encode_array(&strings)).unwrap();
+        assert_eq!(strings, parsed.iter().map(|e| e.as_deref()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn decode_typed_elements() {
+        assert_eq!(vec![1i64, -2, 3], decode_array::<i64>("{1,-2,3}").unwrap());
+        assert_eq!(vec![Some(1i32), None], decode_array::<Option<i32>>("{1,NULL}").unwrap());
+        assert_eq!(vec![true, false], decode_array::<bool>("{t,f}").unwrap());
+        assert!(decode_array::<i32>("{1,NULL}").is_err());
+        assert!(decode_array::<i32>("{1,x}").is_err());
+
+        let floats = decode_array::<f64>("{NaN,Infinity,-Infinity,1.5}").unwrap();
+        assert!(floats[0].is_nan());
+        assert_eq!(vec![f64::INFINITY, f64::NEG_INFINITY, 1.5], floats[1..]);
+    }
+}
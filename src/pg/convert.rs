@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use super::{Connection, SpinPgTypeInfo};
+use super::{Connection, SpinPgTypeInfo, SpinPgValue};
 
 // anyhow::Error makes sqlx mad
 #[derive(Debug)]
@@ -21,6 +21,24 @@ impl Display for BadValError {
     }
 }
 
+#[derive(Debug)]
+struct NotTextError(&'static str);
+impl std::error::Error for NotTextError {}
+impl Display for NotTextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} values must be selected as text, e.g. `SELECT col::text`", self.0)
+    }
+}
+
+// For types that Spin's Postgres interface doesn't pass natively, and which
+// therefore have to be cast to text in the query.
+pub(super) fn text_of(value: SpinPgValue, type_name: &'static str) -> Result<String, sqlx::error::BoxDynError> {
+    match value.inner {
+        spin_sdk::pg::DbValue::Str(s) => Ok(s),
+        _ => Err(Box::new(NotTextError(type_name))),
+    }
+}
+
 fn into_or_err<T: TryInto<U>, U>(value: T) -> Result<U, sqlx::error::BoxDynError> {
     match value.try_into() {
         Ok(v) => Ok(v),
@@ -106,6 +124,9 @@ impl sqlx::Type<Connection> for i32 {
     fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
         SpinPgTypeInfo::Int32
     }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Int16 | SpinPgTypeInfo::Int32)
+    }
 }
 
 impl<'q> sqlx::Encode<'q, Connection> for i64 {
@@ -128,6 +149,9 @@ impl sqlx::Type<Connection> for i64 {
     fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
         SpinPgTypeInfo::Int64
     }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Int16 | SpinPgTypeInfo::Int32 | SpinPgTypeInfo::Int64)
+    }
 }
 
 // --- END INTEGERS ---
@@ -181,6 +205,7 @@ impl<'q> sqlx::Encode<'q, Connection> for f64 {
 impl<'r> sqlx::Decode<'r, Connection> for f64 {
     fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         match value.inner {
+            spin_sdk::pg::DbValue::Floating32(n) => Ok(n.into()),
             spin_sdk::pg::DbValue::Floating64(n) => Ok(n),
             _ => Err(Box::new(BadTypeError)),
         }
@@ -190,6 +215,9 @@ impl sqlx::Type<Connection> for f64 {
     fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
         SpinPgTypeInfo::Floating64
     }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Floating32 | SpinPgTypeInfo::Floating64)
+    }
 }
 
 impl<'q> sqlx::Encode<'q, Connection> for &[u8] {
@@ -239,3 +267,82 @@ impl<'q, T: sqlx::Encode<'q, Connection>> sqlx::Encode<'q, Connection> for Optio
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{FromRow, Row, Type};
+    use spin_sdk::pg::{DbDataType, DbValue};
+
+    use super::super::{Connection, SpinPgRow, SpinPgTypeInfo};
+
+    #[derive(Debug, PartialEq, sqlx::FromRow)]
+    struct Pet {
+        name: String,
+        age: Option<i32>,
+    }
+
+    #[test]
+    fn from_row_decodes_null_as_none() {
+        let pet = Pet::from_row(&SpinPgRow::for_test(vec![
+            ("name", DbDataType::Str, DbValue::Str("Slats".into())),
+            ("age", DbDataType::Int32, DbValue::DbNull),
+        ])).unwrap();
+        assert_eq!(Pet { name: "Slats".into(), age: None }, pet);
+    }
+
+    #[test]
+    fn from_row_decodes_value_as_some() {
+        let pet = Pet::from_row(&SpinPgRow::for_test(vec![
+            ("name", DbDataType::Str, DbValue::Str("Hobbes".into())),
+            ("age", DbDataType::Int32, DbValue::Int32(1)),
+        ])).unwrap();
+        assert_eq!(Pet { name: "Hobbes".into(), age: Some(1) }, pet);
+    }
+
+    #[test]
+    fn scalar_option_decodes_null_and_value() {
+        // `query_scalar` decodes its rows as one-element tuples
+        let (none,) = <(Option<i64>,)>::from_row(&SpinPgRow::for_test(vec![("n", DbDataType::Int64, DbValue::DbNull)])).unwrap();
+        let (some,) = <(Option<i64>,)>::from_row(&SpinPgRow::for_test(vec![("n", DbDataType::Int64, DbValue::Int64(42))])).unwrap();
+        assert_eq!(None, none);
+        assert_eq!(Some(42), some);
+    }
+
+    #[test]
+    fn unsupported_values_are_not_none() {
+        let row = SpinPgRow::for_test(vec![("n", DbDataType::Other, DbValue::Unsupported)]);
+        assert!(row.try_get::<Option<String>, _>("n").is_err());
+    }
+
+    #[test]
+    fn integers_widen() {
+        let row = SpinPgRow::for_test(vec![
+            ("small", DbDataType::Int16, DbValue::Int16(7)),
+            ("int", DbDataType::Int32, DbValue::Int32(70_000)),
+            ("big", DbDataType::Int64, DbValue::Int64(5_000_000_000)),
+        ]);
+        assert_eq!(7, row.try_get::<i32, _>("small").unwrap());
+        assert_eq!(7, row.try_get::<i64, _>("small").unwrap());
+        assert_eq!(Some(70_000), row.try_get::<Option<i64>, _>("int").unwrap());
+        assert!(row.try_get::<i32, _>("big").is_err());
+        assert!(row.try_get::<i16, _>("int").is_err());
+    }
+
+    #[test]
+    fn floats_widen() {
+        let row = SpinPgRow::for_test(vec![("x", DbDataType::Floating32, DbValue::Floating32(1.5))]);
+        assert_eq!(1.5, row.try_get::<f64, _>("x").unwrap());
+        assert_eq!(1.5, row.try_get::<f32, _>("x").unwrap());
+    }
+
+    #[test]
+    fn compatibility_only_widens() {
+        assert!(<i64 as Type<Connection>>::compatible(&SpinPgTypeInfo::Int16));
+        assert!(<i64 as Type<Connection>>::compatible(&SpinPgTypeInfo::Int32));
+        assert!(<i32 as Type<Connection>>::compatible(&SpinPgTypeInfo::Int16));
+        assert!(!<i32 as Type<Connection>>::compatible(&SpinPgTypeInfo::Int64));
+        assert!(<f64 as Type<Connection>>::compatible(&SpinPgTypeInfo::Floating32));
+        assert!(!<f32 as Type<Connection>>::compatible(&SpinPgTypeInfo::Floating64));
+        assert!(!<f64 as Type<Connection>>::compatible(&SpinPgTypeInfo::Int64));
+    }
+}
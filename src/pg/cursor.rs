@@ -0,0 +1,177 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_core::stream::BoxStream;
+use futures_util::TryStreamExt;
+
+use crate::sql::{check_arguments, split_statements, Dialect};
+
+use super::error::AsSqlxResult;
+use super::{Connection, SpinPgArgs, SpinPgRow};
+
+static NEXT_CURSOR: AtomicUsize = AtomicUsize::new(0);
+
+impl Connection {
+    /// Streams the rows of a query through a server-side cursor, fetching
+    /// `batch_size` rows at a time, so that big tables can be read in bounded
+    /// memory.
+    ///
+    /// The cursor lives in a transaction (or a savepoint, if the connection is
+    /// already in one) which is committed once the last row has been read.  If
+    /// the stream is dropped before then, or fails, the transaction is rolled back.
+    ///
+    /// ```no_run
+    /// # async fn export(conn: &mut spin_sqlx::pg::Connection) -> Result<(), sqlx::Error> {
+    /// use futures::TryStreamExt;
+    /// use sqlx::Row;
+    ///
+    /// let mut rows = conn.cursor(sqlx::query("SELECT id, name FROM pets ORDER BY id"), 500);
+    /// while let Some(row) = rows.try_next().await? {
+    ///     let name: String = row.try_get("name")?;
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cursor<'c, 'q, E>(&'c mut self, mut query: E, batch_size: usize) -> BoxStream<'c, Result<SpinPgRow, sqlx::Error>>
+    where
+        'q: 'c,
+        E: 'q + sqlx::Execute<'q, Connection>,
+    {
+        let name = format!("spin_sqlx_cursor_{}", NEXT_CURSOR.fetch_add(1, Ordering::Relaxed));
+
+        let declare = match query.take_arguments() {
+            Ok(args) => {
+                let args = args.unwrap_or_default();
+                let sql = self.sql(query.sql());
+                declare_sql(&name, &sql, args.inner.len()).map(|sql| (sql, args))
+            },
+            Err(e) => Err(sqlx::Error::Encode(e)),
+        };
+        let declare = match declare {
+            Ok(declare) => declare,
+            Err(e) => return Box::pin(futures::stream::once(async move { Err(e) })),
+        };
+
+        let cursor = Cursor {
+            conn: self,
+            name,
+            batch_size: batch_size.max(1),
+            declare: Some(declare),
+            in_transaction: false,
+        };
+
+        let batches = futures::stream::try_unfold(Some(cursor), |cursor| async move {
+            let Some(mut cursor) = cursor else {
+                return Ok::<_, sqlx::Error>(None);
+            };
+            let rows = cursor.fetch().await?;
+            if rows.len() < cursor.batch_size {
+                cursor.close().await?;
+                Ok(Some((rows, None)))
+            } else {
+                Ok(Some((rows, Some(cursor))))
+            }
+        });
+
+        Box::pin(batches.map_ok(|rows| futures::stream::iter(rows.into_iter().map(Ok))).try_flatten())
+    }
+}
+
+// The query goes on a line of its own so that a trailing line comment can't
+// swallow anything after it
+fn declare_sql(name: &str, sql: &str, bound: usize) -> Result<String, sqlx::Error> {
+    match split_statements(sql, Dialect::Postgres)[..] {
+        [sql] => {
+            check_arguments(sql, Dialect::Postgres, bound)?;
+            Ok(format!("DECLARE {name} NO SCROLL CURSOR FOR\n{sql}"))
+        },
+        _ => Err(sqlx::Error::Protocol("a cursor needs SQL containing exactly one query".into())),
+    }
+}
+
+fn fetch_sql(name: &str, batch_size: usize) -> String {
+    format!("FETCH FORWARD {batch_size} FROM {name}")
+}
+
+// Rolls back the cursor's transaction if dropped before reaching the end.
+struct Cursor<'c> {
+    conn: &'c mut Connection,
+    name: String,
+    batch_size: usize,
+    // Declared on the first fetch, so nothing runs until the stream is read
+    declare: Option<(String, SpinPgArgs)>,
+    in_transaction: bool,
+}
+
+impl Cursor<'_> {
+    async fn fetch(&mut self) -> Result<Vec<SpinPgRow>, sqlx::Error> {
+        if let Some((sql, args)) = self.declare.take() {
+            <Connection as sqlx::TransactionManager>::begin(self.conn).await?;
+            self.in_transaction = true;
+            self.conn.inner.execute(&sql, args.as_slice()).as_sqlx_result()?;
+        }
+
+        let sql = fetch_sql(&self.name, self.batch_size);
+        let (columns, rows) = self.conn.query_rows(&sql, &SpinPgArgs::default())?;
+        Ok(rows.into_iter().map(|r| SpinPgRow { columns: columns.clone(), inner: r }).collect())
+    }
+
+    async fn close(mut self) -> Result<(), sqlx::Error> {
+        self.conn.inner.execute(&format!("CLOSE {}", self.name), &[]).as_sqlx_result()?;
+        <Connection as sqlx::TransactionManager>::commit(self.conn).await?;
+        self.in_transaction = false;
+        Ok(())
+    }
+}
+
+impl Drop for Cursor<'_> {
+    fn drop(&mut self) {
+        if self.in_transaction {
+            <Connection as sqlx::TransactionManager>::start_rollback(self.conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol_error(result: Result<String, sqlx::Error>) -> String {
+        match result {
+            Err(sqlx::Error::Protocol(message)) => message,
+            other => panic!("expected a protocol error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn declaring_a_cursor() {
+        assert_eq!(
+            "DECLARE c NO SCROLL CURSOR FOR\nSELECT * FROM pets WHERE age > $1 -- old ones",
+            declare_sql("c", "SELECT * FROM pets WHERE age > $1 -- old ones", 1).unwrap(),
+        );
+        // The statement's own semicolon goes, so the query can be declared
+        assert_eq!("DECLARE c NO SCROLL CURSOR FOR\nSELECT 1", declare_sql("c", "  SELECT 1;\n", 0).unwrap());
+        assert_eq!("DECLARE c NO SCROLL CURSOR FOR\nSELECT ';'", declare_sql("c", "SELECT ';'; -- done", 0).unwrap());
+    }
+
+    #[test]
+    fn cursors_need_exactly_one_query() {
+        for sql in ["SELECT 1; SELECT 2", "", " ; -- nothing"] {
+            assert_eq!("a cursor needs SQL containing exactly one query", protocol_error(declare_sql("c", sql, 0)), "{sql:?}");
+        }
+    }
+
+    #[test]
+    fn cursors_check_their_arguments() {
+        assert_eq!(
+            "query expects 2 parameters, 1 bound (placeholders: $1 at 1:27, $2 at 1:38)",
+            protocol_error(declare_sql("c", "SELECT * FROM t WHERE a = $1 AND b = $2", 1)),
+        );
+        assert_eq!("query expects 0 parameters, 1 bound", protocol_error(declare_sql("c", "SELECT 1", 1)));
+    }
+
+    #[test]
+    fn fetching_from_a_cursor() {
+        assert_eq!("FETCH FORWARD 500 FROM spin_sqlx_cursor_3", fetch_sql("spin_sqlx_cursor_3", 500));
+    }
+}
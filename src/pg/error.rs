@@ -37,6 +37,31 @@ impl AsSqlxError for spin_sdk::pg::PgError {
     }
 }
 
+pub(crate) fn unsupported_column(column: &spin_sdk::pg::Column) -> sqlx::Error {
+    sqlx::Error::ColumnDecode {
+        index: format!("{:?}", column.name),
+        source: Box::new(UnsupportedColumn { name: column.name.clone(), data_type: column.data_type }),
+    }
+}
+
+#[derive(Debug)]
+struct UnsupportedColumn {
+    name: String,
+    data_type: spin_sdk::pg::DbDataType,
+}
+
+impl std::error::Error for UnsupportedColumn {}
+
+impl std::fmt::Display for UnsupportedColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "column {:?} has a type Spin does not support (data type {:?}): try casting it in the query, e.g. `{}::text`",
+            self.name, self.data_type, self.name
+        )
+    }
+}
+
 fn io_error(message: &str) -> sqlx::error::Error {
     sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, anyhow!(message.to_owned())))
 }
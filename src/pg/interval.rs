@@ -0,0 +1,335 @@
+// Spin's Postgres interface doesn't pass intervals natively, so they travel
+// as text: cast parameters in SQL (`VALUES ($1::text::interval)`) and select
+// columns as text (`SELECT duration::text FROM sessions`).
+
+use std::fmt::Display;
+
+use super::convert::text_of;
+use super::{Connection, SpinPgTypeInfo};
+
+/// A Postgres `INTERVAL`.
+///
+/// As in Postgres, months and days are kept separate from the time part,
+/// because their lengths vary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SpinPgInterval {
+    pub months: i32,
+    pub days: i32,
+    pub microseconds: i64,
+}
+
+#[derive(Debug)]
+struct BadIntervalError(String);
+impl std::error::Error for BadIntervalError {}
+impl Display for BadIntervalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad interval value: {}", self.0)
+    }
+}
+
+fn bad_interval(message: impl Into<String>) -> sqlx::error::BoxDynError {
+    Box::new(BadIntervalError(message.into()))
+}
+
+fn in_range<T>(value: Option<T>) -> Result<T, sqlx::error::BoxDynError> {
+    value.ok_or_else(|| bad_interval("out of range"))
+}
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+
+impl SpinPgInterval {
+    /// Parses the `postgres` (the default), `postgres_verbose` or `iso_8601`
+    /// `IntervalStyle` text output.
+    pub fn parse(text: &str) -> Result<Self, sqlx::error::BoxDynError> {
+        let text = text.trim();
+        match text.strip_prefix('P') {
+            Some(iso) => parse_iso_8601(iso),
+            None => parse_postgres(text),
+        }
+    }
+}
+
+// Units are accumulated with checked arithmetic, so that out-of-range text is
+// an error rather than a panic (or, in release builds, a wrong value).
+impl SpinPgInterval {
+    fn add_months(&mut self, count: i32, months_per_unit: i32) -> Result<(), sqlx::error::BoxDynError> {
+        self.months = in_range(count.checked_mul(months_per_unit).and_then(|m| self.months.checked_add(m)))?;
+        Ok(())
+    }
+
+    fn add_days(&mut self, count: i32, days_per_unit: i32) -> Result<(), sqlx::error::BoxDynError> {
+        self.days = in_range(count.checked_mul(days_per_unit).and_then(|d| self.days.checked_add(d)))?;
+        Ok(())
+    }
+
+    fn add_microseconds(&mut self, count: i64, micros_per_unit: i64) -> Result<(), sqlx::error::BoxDynError> {
+        self.microseconds = in_range(count.checked_mul(micros_per_unit).and_then(|m| self.microseconds.checked_add(m)))?;
+        Ok(())
+    }
+
+    fn negated(self) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(Self {
+            months: in_range(self.months.checked_neg())?,
+            days: in_range(self.days.checked_neg())?,
+            microseconds: in_range(self.microseconds.checked_neg())?,
+        })
+    }
+}
+
+impl Display for SpinPgInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} mons {} days {} microseconds", self.months, self.days, self.microseconds)
+    }
+}
+
+impl std::str::FromStr for SpinPgInterval {
+    type Err = sqlx::error::BoxDynError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<std::time::Duration> for SpinPgInterval {
+    type Error = sqlx::error::BoxDynError;
+
+    fn try_from(value: std::time::Duration) -> Result<Self, Self::Error> {
+        let microseconds = value.as_micros().try_into()
+            .map_err(|_| bad_interval("duration is too long for an interval"))?;
+        Ok(Self { months: 0, days: 0, microseconds })
+    }
+}
+
+// Parses seconds with an optional fraction, e.g. `06.789`, into microseconds.
+fn parse_seconds(text: &str) -> Result<i64, sqlx::error::BoxDynError> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(t) => (true, t),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (whole, frac) = text.split_once('.').unwrap_or((text, ""));
+    if frac.len() > 6 || !frac.chars().all(|c| c.is_ascii_digit()) {
+        return Err(bad_interval(format!("'{text}' is not a valid number of seconds")));
+    }
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse()? };
+    let frac: i64 = if frac.is_empty() { 0 } else { format!("{frac:0<6}").parse()? };
+    let micros = in_range(whole.checked_mul(MICROS_PER_SECOND).and_then(|w| w.checked_add(frac)))?;
+    Ok(if negative { -micros } else { micros })
+}
+
+// Parses `[+-]HH:MM[:SS[.ffffff]]` into microseconds.
+fn parse_time(text: &str) -> Result<i64, sqlx::error::BoxDynError> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(t) => (true, t),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let mut parts = text.split(':');
+    let hours: i64 = parts.next().unwrap_or_default().parse()?;
+    let minutes: i64 = parts.next().ok_or_else(|| bad_interval(format!("'{text}' is not a valid time")))?.parse()?;
+    let seconds = match parts.next() {
+        Some(s) => parse_seconds(s)?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return Err(bad_interval(format!("'{text}' is not a valid time")));
+    }
+    let micros = hours.checked_mul(MICROS_PER_HOUR)
+        .zip(minutes.checked_mul(MICROS_PER_MINUTE))
+        .and_then(|(h, m)| h.checked_add(m))
+        .and_then(|hm| hm.checked_add(seconds));
+    let micros = in_range(micros)?;
+    Ok(if negative { -micros } else { micros })
+}
+
+fn parse_postgres(text: &str) -> Result<SpinPgInterval, sqlx::error::BoxDynError> {
+    let mut interval = SpinPgInterval::default();
+    let mut ago = false;
+
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token == "@" {
+            continue;
+        }
+        if token == "ago" {
+            ago = true;
+            continue;
+        }
+        if token.contains(':') {
+            interval.add_microseconds(parse_time(token)?, 1)?;
+            continue;
+        }
+
+        let unit = tokens.next().ok_or_else(|| bad_interval(format!("'{token}' has no unit")))?;
+        match unit.trim_end_matches('s') {
+            "year" => interval.add_months(token.parse()?, 12)?,
+            "mon" | "month" => interval.add_months(token.parse()?, 1)?,
+            "day" => interval.add_days(token.parse()?, 1)?,
+            "hour" => interval.add_microseconds(token.parse()?, MICROS_PER_HOUR)?,
+            "min" | "minute" => interval.add_microseconds(token.parse()?, MICROS_PER_MINUTE)?,
+            "sec" | "second" => interval.add_microseconds(parse_seconds(token)?, 1)?,
+            // Not in Postgres' output, but this is how `Display` writes the time part
+            "microsecond" => interval.add_microseconds(token.parse()?, 1)?,
+            _ => return Err(bad_interval(format!("unknown unit '{unit}'"))),
+        }
+    }
+
+    if ago {
+        interval = interval.negated()?;
+    }
+
+    Ok(interval)
+}
+
+fn parse_iso_8601(text: &str) -> Result<SpinPgInterval, sqlx::error::BoxDynError> {
+    let mut interval = SpinPgInterval::default();
+    let mut in_time = false;
+    let mut number = String::new();
+
+    for c in text.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' | '-' | '+' | '.' => number.push(c),
+            designator => {
+                match (in_time, designator) {
+                    (false, 'Y') => interval.add_months(number.parse()?, 12)?,
+                    (false, 'M') => interval.add_months(number.parse()?, 1)?,
+                    (false, 'W') => interval.add_days(number.parse()?, 7)?,
+                    (false, 'D') => interval.add_days(number.parse()?, 1)?,
+                    (true, 'H') => interval.add_microseconds(number.parse()?, MICROS_PER_HOUR)?,
+                    (true, 'M') => interval.add_microseconds(number.parse()?, MICROS_PER_MINUTE)?,
+                    (true, 'S') => interval.add_microseconds(parse_seconds(&number)?, 1)?,
+                    _ => return Err(bad_interval(format!("unexpected '{designator}' in 'P{text}'"))),
+                }
+                number.clear();
+            },
+        }
+    }
+
+    if !number.is_empty() {
+        return Err(bad_interval(format!("'P{text}' has a trailing number with no unit")));
+    }
+
+    Ok(interval)
+}
+
+impl<'q> sqlx::Encode<'q, Connection> for SpinPgInterval {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(self.to_string()));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+impl<'r> sqlx::Decode<'r, Connection> for SpinPgInterval {
+    fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Self::parse(&text_of(value, "interval")?)
+    }
+}
+impl sqlx::Type<Connection> for SpinPgInterval {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        SpinPgTypeInfo::Interval
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Interval | SpinPgTypeInfo::Str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(months: i32, days: i32, microseconds: i64) -> SpinPgInterval {
+        SpinPgInterval { months, days, microseconds }
+    }
+
+    #[test]
+    fn parse_postgres_style() {
+        assert_eq!(interval(0, 0, 0), SpinPgInterval::parse("00:00:00").unwrap());
+        assert_eq!(interval(14, 3, 4 * MICROS_PER_HOUR + 5 * MICROS_PER_MINUTE + 6_789_000), SpinPgInterval::parse("1 year 2 mons 3 days 04:05:06.789").unwrap());
+        assert_eq!(interval(0, 1, 0), SpinPgInterval::parse("1 day").unwrap());
+        assert_eq!(interval(0, 0, 100 * MICROS_PER_HOUR), SpinPgInterval::parse("100:00:00").unwrap());
+    }
+
+    #[test]
+    fn parse_postgres_mixed_signs() {
+        assert_eq!(
+            interval(-12 + 2, -3, 4 * MICROS_PER_HOUR + 5 * MICROS_PER_MINUTE + 6_000_000),
+            SpinPgInterval::parse("-1 years +2 mons -3 days +04:05:06").unwrap()
+        );
+        assert_eq!(interval(0, 1, -(4 * MICROS_PER_HOUR + 500_000)), SpinPgInterval::parse("1 day -04:00:00.5").unwrap());
+    }
+
+    #[test]
+    fn parse_postgres_verbose_style() {
+        assert_eq!(
+            interval(14, 3, 4 * MICROS_PER_HOUR + 5 * MICROS_PER_MINUTE + 6_789_000),
+            SpinPgInterval::parse("@ 1 year 2 mons 3 days 4 hours 5 mins 6.789 secs").unwrap()
+        );
+        assert_eq!(interval(-1, -2, -3 * MICROS_PER_MINUTE), SpinPgInterval::parse("@ 1 mon 2 days 3 mins ago").unwrap());
+        assert_eq!(interval(0, 0, 1_000_000 - MICROS_PER_MINUTE), SpinPgInterval::parse("@ 1 min -1 secs ago").unwrap());
+    }
+
+    #[test]
+    fn parse_fractional_seconds() {
+        assert_eq!(interval(0, 0, 1), SpinPgInterval::parse("00:00:00.000001").unwrap());
+        assert_eq!(interval(0, 0, 500_000), SpinPgInterval::parse("0.5 secs").unwrap());
+        assert_eq!(interval(0, 0, -1_250_000), SpinPgInterval::parse("-00:00:01.25").unwrap());
+        assert!(SpinPgInterval::parse("00:00:00.0000001").is_err());
+        assert!(SpinPgInterval::parse("00:00:0x").is_err());
+    }
+
+    #[test]
+    fn parse_iso_8601_style() {
+        assert_eq!(interval(14, 3, 4 * MICROS_PER_HOUR + 5 * MICROS_PER_MINUTE + 6_789_000), SpinPgInterval::parse("P1Y2M3DT4H5M6.789S").unwrap());
+        assert_eq!(interval(0, 14, 0), SpinPgInterval::parse("P2W").unwrap());
+        assert_eq!(interval(0, 0, 90 * MICROS_PER_MINUTE), SpinPgInterval::parse("PT1H30M").unwrap());
+        assert_eq!(interval(-10, 3, -(4 * MICROS_PER_HOUR) - 500_000), SpinPgInterval::parse("P-1Y2M3DT-4H-0.5S").unwrap());
+        assert_eq!(interval(0, 0, 0), SpinPgInterval::parse("PT0S").unwrap());
+    }
+
+    #[test]
+    fn parse_rejects_malformed_iso_8601() {
+        assert!(SpinPgInterval::parse("P1").is_err());
+        assert!(SpinPgInterval::parse("P1H").is_err());
+        assert!(SpinPgInterval::parse("PT1D").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_unknown_units() {
+        assert!(SpinPgInterval::parse("3 fortnights").is_err());
+        assert!(SpinPgInterval::parse("3").is_err());
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert!(SpinPgInterval::parse("178956971 years").is_err());
+        assert!(SpinPgInterval::parse("2147483647 mons 1 mon").is_err());
+        assert!(SpinPgInterval::parse("2147483647 days 1 day").is_err());
+        assert!(SpinPgInterval::parse("P2147483647M1Y").is_err());
+        assert!(SpinPgInterval::parse("P1000000000W").is_err());
+        assert!(SpinPgInterval::parse("2562047789 hours").is_err());
+        assert!(SpinPgInterval::parse("PT153722867281M").is_err());
+        assert!(SpinPgInterval::parse("9223372036855 secs").is_err());
+        assert!(SpinPgInterval::parse("2562047789:00:00").is_err());
+        assert!(SpinPgInterval::parse("2562047788:00:00 1 hour").is_err());
+        assert!(SpinPgInterval::parse("@ -2147483648 mons ago").is_err());
+    }
+
+    #[test]
+    fn extremes_in_range_parse() {
+        assert_eq!(interval(i32::MAX, i32::MIN, 0), SpinPgInterval::parse("2147483647 mons -2147483648 days").unwrap());
+        assert_eq!(interval(0, 0, 2562047788 * MICROS_PER_HOUR), SpinPgInterval::parse("2562047788:00:00").unwrap());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let value = interval(-14, 3, -(4 * MICROS_PER_HOUR) - 1);
+        assert_eq!(value, SpinPgInterval::parse(&value.to_string()).unwrap());
+    }
+
+    #[test]
+    fn from_duration() {
+        let value = SpinPgInterval::try_from(std::time::Duration::from_millis(1500)).unwrap();
+        assert_eq!(interval(0, 0, 1_500_000), value);
+        assert!(SpinPgInterval::try_from(std::time::Duration::MAX).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+use futures_core::future::BoxFuture;
+use sqlx::migrate::{AppliedMigration, Migrate, MigrateError, Migration};
+use sqlx::{query, query_as, Executor};
+
+use crate::sql::{split_statements, Dialect};
+
+use super::Connection;
+
+// Spin Postgres runs only one statement per call, so scripts must be split up
+async fn execute_script(conn: &Connection, sql: &str) -> Result<(), sqlx::Error> {
+    for statement in split_statements(sql, Dialect::Postgres) {
+        conn.execute(statement).await?;
+    }
+    Ok(())
+}
+
+// The same lock ID as sqlx's own Postgres migrator uses, so that Spin and
+// native migrators exclude each other.
+async fn lock_id(conn: &Connection) -> Result<i64, sqlx::Error> {
+    let (database_name,): (String,) = query_as("SELECT current_database()::text").fetch_one(conn).await?;
+    Ok(generate_lock_id(&database_name))
+}
+
+// As sqlx-postgres's function of the same name
+fn generate_lock_id(database_name: &str) -> i64 {
+    0x3d32ad9e * i64::from(crc32(database_name.as_bytes()))
+}
+
+// CRC-32 (IEEE)
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+async fn finish_transaction(conn: &Connection, result: Result<(), MigrateError>) -> Result<(), MigrateError> {
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT").await?;
+            Ok(())
+        },
+        Err(e) => {
+            // The original error is more use than any failure to roll back
+            let _ = conn.execute("ROLLBACK").await;
+            Err(e)
+        },
+    }
+}
+
+/// Lets `sqlx::migrate::Migrator` apply migrations over a connection:
+///
+/// ```no_run
+/// # async fn migrate(conn: &mut spin_sqlx::pg::Connection) -> Result<(), sqlx::migrate::MigrateError> {
+/// // or `static MIGRATOR: Migrator = sqlx::migrate!();`
+/// let migrator = sqlx::migrate::Migrator::new(std::path::Path::new("./migrations")).await?;
+/// migrator.run(conn).await?;
+/// # Ok(())
+/// # }
+/// ```
+impl Migrate for Connection {
+    fn ensure_migrations_table(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(
+                r#"
+CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    installed_on TIMESTAMPTZ NOT NULL DEFAULT now(),
+    success BOOLEAN NOT NULL,
+    checksum BYTEA NOT NULL,
+    execution_time BIGINT NOT NULL
+)
+                "#,
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn dirty_version(&mut self) -> BoxFuture<'_, Result<Option<i64>, MigrateError>> {
+        Box::pin(async move {
+            let row: Option<(i64,)> = query_as(
+                "SELECT version FROM _sqlx_migrations WHERE success = false ORDER BY version LIMIT 1",
+            )
+            .fetch_optional(&*self)
+            .await?;
+
+            Ok(row.map(|r| r.0))
+        })
+    }
+
+    fn list_applied_migrations(&mut self) -> BoxFuture<'_, Result<Vec<AppliedMigration>, MigrateError>> {
+        Box::pin(async move {
+            let rows: Vec<(i64, Vec<u8>)> =
+                query_as("SELECT version, checksum FROM _sqlx_migrations ORDER BY version")
+                    .fetch_all(&*self)
+                    .await?;
+
+            let migrations = rows
+                .into_iter()
+                .map(|(version, checksum)| AppliedMigration { version, checksum: checksum.into() })
+                .collect();
+
+            Ok(migrations)
+        })
+    }
+
+    fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            let lock_id = lock_id(&*self).await?;
+            query("SELECT pg_advisory_lock($1)").bind(lock_id).execute(&*self).await?;
+            Ok(())
+        })
+    }
+
+    fn unlock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            let lock_id = lock_id(&*self).await?;
+            query("SELECT pg_advisory_unlock($1)").bind(lock_id).execute(&*self).await?;
+            Ok(())
+        })
+    }
+
+    fn apply<'e: 'm, 'm>(&'e mut self, migration: &'m Migration) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(async move {
+            let conn = &*self;
+            let start = Instant::now();
+
+            // As in sqlx, the script and the bookkeeping share a transaction so that a
+            // migration can never be applied twice.  The execution time is recorded
+            // afterwards, so it's -1 until then.
+            let run = async {
+                execute_script(conn, &migration.sql).await
+                    .map_err(|e| MigrateError::ExecuteMigration(e, migration.version))?;
+
+                query(
+                    r#"
+    INSERT INTO _sqlx_migrations ( version, description, success, checksum, execution_time )
+    VALUES ( $1, $2, TRUE, $3, -1 )
+                    "#,
+                )
+                .bind(migration.version)
+                .bind(&*migration.description)
+                .bind(&*migration.checksum)
+                .execute(conn)
+                .await?;
+
+                Ok(())
+            };
+
+            if migration.no_tx {
+                run.await?;
+            } else {
+                conn.execute("BEGIN").await?;
+                finish_transaction(conn, run.await).await?;
+            }
+
+            let elapsed = start.elapsed();
+
+            query(
+                r#"
+    UPDATE _sqlx_migrations
+    SET execution_time = $1
+    WHERE version = $2
+                "#,
+            )
+            .bind(elapsed.as_nanos() as i64)
+            .bind(migration.version)
+            .execute(conn)
+            .await?;
+
+            Ok(elapsed)
+        })
+    }
+
+    fn revert<'e: 'm, 'm>(&'e mut self, migration: &'m Migration) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(async move {
+            let conn = &*self;
+            let start = Instant::now();
+
+            let run = async {
+                execute_script(conn, &migration.sql).await
+                    .map_err(|e| MigrateError::ExecuteMigration(e, migration.version))?;
+
+                query("DELETE FROM _sqlx_migrations WHERE version = $1")
+                    .bind(migration.version)
+                    .execute(conn)
+                    .await?;
+
+                Ok(())
+            };
+
+            if migration.no_tx {
+                run.await?;
+            } else {
+                conn.execute("BEGIN").await?;
+                finish_transaction(conn, run.await).await?;
+            }
+
+            Ok(start.elapsed())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_values() {
+        assert_eq!(0, crc32(b""));
+        assert_eq!(0xcbf43926, crc32(b"123456789"));
+        assert_eq!(0x414fa339, crc32(b"The quick brown fox jumps over the lazy dog"));
+    }
+
+    #[test]
+    fn lock_ids_match_sqlx() {
+        // From sqlx-postgres's `generate_lock_id` (CRC-32/ISO-HDLC times 0x3d32ad9e)
+        assert_eq!(368907709894077238, generate_lock_id("postgres"));
+        assert_eq!(642395809610723702, generate_lock_id("app_db"));
+
+        // The same CRC as sqlx uses
+        const CRC_IEEE: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+        for name in ["", "postgres", "app_db", "Ünïcode", "a much longer database name than most"] {
+            assert_eq!(0x3d32ad9e * (CRC_IEEE.checksum(name.as_bytes()) as i64), generate_lock_id(name), "{name}");
+        }
+    }
+}
@@ -1,33 +1,184 @@
+use std::borrow::Cow;
+use std::future::ready;
+
 use futures_core::future::BoxFuture;
 use futures_core::stream::BoxStream;
-use log::LevelFilter;
+use futures_util::{StreamExt, TryStreamExt};
 
+mod any;
+mod array;
 mod convert;
+mod cursor;
 mod error;
+mod interval;
+mod migrate;
+mod money;
+mod net;
+mod options;
+mod pool;
 mod query_result;
 mod type_info;
 
+pub use any::ANY_DRIVER;
+pub use array::{DecodeArrayElement, EncodeArrayElement, PgHasArrayType};
+pub use interval::SpinPgInterval;
+pub use money::SpinPgMoney;
+pub use options::ConnectionOptions;
+pub use pool::{SpinPgPool, SpinPgPoolConnection};
 pub use query_result::{SpinPgColumn, SpinPgQueryResult, SpinPgRow, SpinPgValue};
 pub use type_info::SpinPgTypeInfo;
 
 use error::AsSqlxResult;
 
+use crate::sql::{
+    arguments_for_several_statements, check_arguments, counts_rows, page_sql, pageable_query, statements,
+    translate_placeholders, words, Dialect,
+};
+
 #[derive(Debug)]
-pub struct Connection(spin_sdk::pg::Connection);
+pub struct Connection {
+    inner: spin_sdk::pg::Connection,
+    reject_unsupported_columns: bool,
+    fetch_page_size: Option<usize>,
+    portable_placeholders: bool,
+    transaction_depth: usize,
+}
 
 impl Connection {
     pub fn new(conn: spin_sdk::pg::Connection) -> Self {
-        Self(conn)
+        Self {
+            inner: conn,
+            reject_unsupported_columns: false,
+            fetch_page_size: None,
+            portable_placeholders: false,
+            transaction_depth: 0,
+        }
     }
 
     pub fn open(address: &str) -> anyhow::Result<Self> {
-        Ok(Self(spin_sdk::pg::Connection::open(address)?))
+        Ok(Self::new(spin_sdk::pg::Connection::open(address)?))
+    }
+
+    /// The Spin connection, for things this crate doesn't support.  Statements
+    /// run on it share this connection's transaction, if there is one.
+    pub fn as_inner(&self) -> &spin_sdk::pg::Connection {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> spin_sdk::pg::Connection {
+        self.inner
+    }
+
+    /// Fail any query whose results include a column of a type that Spin doesn't
+    /// support, rather than failing only when a value from that column is decoded.
+    pub fn reject_unsupported_columns(mut self, reject: bool) -> Self {
+        self.reject_unsupported_columns = reject;
+        self
+    }
+
+    /// Streams the rows of queries (`fetch`, `fetch_many`) a page of `size` rows
+    /// at a time, rather than reading them all into memory at once.  Each page is
+    /// read only when the rows before it have been, by running the query again
+    /// wrapped in `LIMIT ... OFFSET ...`, so give such queries an `ORDER BY` to
+    /// keep pages from overlapping.  Queries with a `LIMIT`, `OFFSET` or `FETCH`
+    /// of their own, or which lock rows (`FOR UPDATE`, `FOR SHARE`, ...), are
+    /// read whole.  A size of 0, the default, turns paging off.
+    pub fn fetch_page_size(mut self, size: usize) -> Self {
+        self.fetch_page_size = (size > 0).then_some(size);
+        self
+    }
+
+    /// Accepts SQLite-style `?` and `?N` placeholders as well as Postgres's own,
+    /// by rewriting them to `$N`, so that the same SQL can run on both backends.
+    /// Placeholders in string literals, quoted identifiers and comments are
+    /// left alone, but `?` operators are not, so use the `jsonb_exists` family
+    /// of functions instead of `jsonb`'s `?`, `?|` and `?&`.
+    pub fn portable_placeholders(mut self, portable: bool) -> Self {
+        self.portable_placeholders = portable;
+        self
+    }
+
+    // The SQL to run for a query
+    fn sql<'q>(&self, sql: &'q str) -> Cow<'q, str> {
+        if self.portable_placeholders {
+            translate_placeholders(sql, Dialect::Postgres)
+        } else {
+            Cow::Borrowed(sql)
+        }
+    }
+
+    // Runs the query for each page only once the rows of the page before it
+    // have been read
+    fn fetch_pages<'a>(
+        &'a self,
+        sql: String,
+        args: SpinPgArgs,
+        page_size: usize,
+    ) -> impl futures_core::Stream<Item = Result<sqlx::Either<SpinPgQueryResult, SpinPgRow>, sqlx::Error>> + 'a {
+        futures::stream::unfold(Some(0), move |offset| {
+            let page = offset.map(|offset| match self.query_rows(&page_sql(&sql, page_size, offset), &args) {
+                Ok((columns, rows)) => {
+                    let read = rows.len();
+                    let mut steps = rows.into_iter()
+                        .map(|r| sqlx::Either::Right(SpinPgRow { columns: columns.clone(), inner: r }))
+                        .collect::<Vec<_>>();
+                    if read < page_size {
+                        steps.push(sqlx::Either::Left(SpinPgQueryResult { count: 0 }));
+                        (Ok(steps), None)
+                    } else {
+                        (Ok(steps), Some(offset + read))
+                    }
+                },
+                Err(e) => (Err(e), None),
+            });
+            ready(page)
+        })
+        .map_ok(|steps| futures::stream::iter(steps.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    fn query_rows(&self, sql: &str, args: &SpinPgArgs) -> Result<(std::sync::Arc<Vec<SpinPgColumn>>, Vec<spin_sdk::pg::Row>), sqlx::Error> {
+        let rs = self.inner.query(sql, args.as_slice()).as_sqlx_result()?;
+
+        if self.reject_unsupported_columns {
+            if let Some(column) = rs.columns.iter().find(|c| c.data_type == spin_sdk::pg::DbDataType::Other) {
+                return Err(error::unsupported_column(column));
+            }
+        }
+
+        let columns = rs.columns.iter().enumerate().map(|(i, c)| SpinPgColumn::new(i, c)).collect::<Vec<_>>();
+        Ok((std::sync::Arc::new(columns), rs.rows))
+    }
+
+    // A statement's rows, followed by its result.  Spin only counts rows for
+    // statements which don't return them.
+    fn statement_steps(&self, sql: &str, args: &SpinPgArgs) -> Result<Vec<sqlx::Either<SpinPgQueryResult, SpinPgRow>>, sqlx::Error> {
+        let counts = counts_rows(sql, Dialect::Postgres);
+        if counts && !words(sql, Dialect::Postgres).any(|w| w.eq_ignore_ascii_case("RETURNING")) {
+            let count = self.inner.execute(sql, args.as_slice()).as_sqlx_result()?;
+            return Ok(vec![sqlx::Either::Left(SpinPgQueryResult { count })]);
+        }
+
+        let (columns, rows) = self.query_rows(sql, args)?;
+        let count = if counts { rows.len() as u64 } else { 0 };
+        let mut steps = rows.into_iter()
+            .map(|r| sqlx::Either::Right(SpinPgRow { columns: columns.clone(), inner: r }))
+            .collect::<Vec<_>>();
+        steps.push(sqlx::Either::Left(SpinPgQueryResult { count }));
+        Ok(steps)
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct ConnectionOptions {
-    address: String,
+impl From<spin_sdk::pg::Connection> for Connection {
+    fn from(conn: spin_sdk::pg::Connection) -> Self {
+        Self::new(conn)
+    }
+}
+
+impl From<Connection> for spin_sdk::pg::Connection {
+    fn from(conn: Connection) -> Self {
+        conn.inner
+    }
 }
 
 impl sqlx::Connection for Connection {
@@ -49,7 +200,7 @@ impl sqlx::Connection for Connection {
     fn begin(&mut self) -> BoxFuture<'_, Result<sqlx::Transaction<'_, Self::Database>, sqlx::Error>>
     where
         Self: Sized {
-        todo!()
+        sqlx::Transaction::begin(self)
     }
 
     fn shrink_buffers(&mut self) {
@@ -89,6 +240,29 @@ impl sqlx::Database for Connection {
     const URL_SCHEMES: &'static [&'static str] = &["spin-pg"];
 }
 
+/// The arguments bound to a Postgres query.
+///
+/// These number their placeholders `$1`, `$2`, ..., so `sqlx::QueryBuilder`
+/// works:
+///
+/// ```
+/// use spin_sqlx::pg::Connection as Postgres;
+///
+/// let pets = [("Fido", 3), ("Rex", 5)];
+/// let mut builder = sqlx::QueryBuilder::<Postgres>::new("INSERT INTO pets (name, age) ");
+/// builder.push_values(pets, |mut row, (name, age)| {
+///     row.push_bind(name).push_bind(age);
+/// });
+/// assert_eq!("INSERT INTO pets (name, age) VALUES ($1, $2), ($3, $4)", builder.sql());
+///
+/// let mut builder = sqlx::QueryBuilder::<Postgres>::new("SELECT * FROM pets WHERE age IN (");
+/// let mut ages = builder.separated(", ");
+/// for age in [1, 2, 3] {
+///     ages.push_bind(age);
+/// }
+/// builder.push(")");
+/// assert_eq!("SELECT * FROM pets WHERE age IN ($1, $2, $3)", builder.sql());
+/// ```
 #[derive(Default)]
 pub struct SpinPgArgs {
     inner: Vec<spin_sdk::pg::ParameterValue>,
@@ -117,6 +291,11 @@ impl<'q> sqlx::Arguments<'q> for SpinPgArgs {
     fn len(&self) -> usize {
         self.inner.len()
     }
+
+    // Called after the argument is added, so its number is the count so far
+    fn format_placeholder<W: std::fmt::Write>(&self, writer: &mut W) -> std::fmt::Result {
+        write!(writer, "${}", self.inner.len())
+    }
 }
 
 impl<'q> sqlx::IntoArguments<'q, Connection> for SpinPgArgs {
@@ -201,65 +380,57 @@ impl<'q> sqlx::Statement<'q> for SpinPgStmt {
     }
 }
 
+sqlx_core::impl_acquire!(Connection, Connection);
+
 impl sqlx::TransactionManager for Connection {
     type Database = Connection;
 
+    // Nested transactions are savepoints, as in sqlx's own drivers
     fn begin(
-        _conn: &mut <Self::Database as sqlx::Database>::Connection,
+        conn: &mut <Self::Database as sqlx::Database>::Connection,
     ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
-        todo!("transactions are not implemented for Spin SQLite")
+        Box::pin(async move {
+            let sql = sqlx_core::transaction::begin_ansi_transaction_sql(conn.transaction_depth);
+            sqlx::Executor::execute(&*conn, &*sql).await?;
+            conn.transaction_depth += 1;
+            Ok(())
+        })
     }
 
     fn commit(
-        _conn: &mut <Self::Database as sqlx::Database>::Connection,
+        conn: &mut <Self::Database as sqlx::Database>::Connection,
     ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
-        todo!("transactions are not implemented for Spin SQLite")
+        Box::pin(async move {
+            if conn.transaction_depth > 0 {
+                let sql = sqlx_core::transaction::commit_ansi_transaction_sql(conn.transaction_depth);
+                sqlx::Executor::execute(&*conn, &*sql).await?;
+                conn.transaction_depth -= 1;
+            }
+            Ok(())
+        })
     }
 
     fn rollback(
-        _conn: &mut <Self::Database as sqlx::Database>::Connection,
+        conn: &mut <Self::Database as sqlx::Database>::Connection,
     ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
-        todo!("transactions are not implemented for Spin SQLite")
-    }
-
-    fn start_rollback(_conn: &mut <Self::Database as sqlx::Database>::Connection) {
-        todo!("transactions are not implemented for Spin SQLite")
-    }
-}
-
-impl std::str::FromStr for ConnectionOptions {
-    type Err = sqlx::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self { address: s.to_owned() })
-    }
-}
-
-impl sqlx::ConnectOptions for ConnectionOptions {
-    type Connection = Connection;
-
-    fn from_url(url: &url::Url) -> Result<Self, sqlx::Error> {
-        let address = url.to_string();
-        Ok(Self { address })
-    }
-
-    fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, sqlx::Error>>
-    where
-        Self::Connection: Sized
-    {
         Box::pin(async move {
-            spin_sdk::pg::Connection::open(&self.address)
-                .map(|conn| Connection(conn))
-                .map_err(|e| sqlx::Error::AnyDriverError(Box::new(e)))
+            if conn.transaction_depth > 0 {
+                let sql = sqlx_core::transaction::rollback_ansi_transaction_sql(conn.transaction_depth);
+                sqlx::Executor::execute(&*conn, &*sql).await?;
+                conn.transaction_depth -= 1;
+            }
+            Ok(())
         })
     }
 
-    fn log_statements(self, _level: LevelFilter) -> Self {
-        self
-    }
-
-    fn log_slow_statements(self, _level: LevelFilter, _duration: std::time::Duration) -> Self {
-        self
+    // Called when a transaction is dropped without being committed.  Spin runs
+    // statements synchronously, so this can roll back there and then.
+    fn start_rollback(conn: &mut <Self::Database as sqlx::Database>::Connection) {
+        if conn.transaction_depth > 0 {
+            let sql = sqlx_core::transaction::rollback_ansi_transaction_sql(conn.transaction_depth);
+            let _ = conn.inner.execute(&sql, &[]);
+            conn.transaction_depth -= 1;
+        }
     }
 }
 
@@ -288,21 +459,35 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
                 return Box::pin(futures::stream::once(async move { Err(sqlx::Error::Encode(e)) }));
             }
         };
-        let rs = match self.0.query(query.sql(), args.as_slice()).as_sqlx_result() {
-            Ok(rs) => rs,
-            Err(e) => {
-                return Box::pin(futures::stream::once(async move { Err(e) }));
+        let sql = self.sql(query.sql());
+        if let Err(e) = check_arguments(&sql, Dialect::Postgres, args.inner.len()) {
+            return Box::pin(futures::stream::once(async move { Err(e) }));
+        }
+        if let Some(page_size) = self.fetch_page_size {
+            if let Some(sql) = pageable_query(&sql, Dialect::Postgres) {
+                return Box::pin(self.fetch_pages(sql.to_owned(), args, page_size));
             }
-        };
-
-        // Okay this CANNOT return a QueryResult because fetch will filtermap any
-        // Either::Lefts away because reasons.  We have to get the rows.
-
-        let columns_core = rs.columns.iter().enumerate().map(|(i, c)| SpinPgColumn::new(i, c)).collect::<Vec<_>>();
-        let columns = std::sync::Arc::new(columns_core);
-        let rows = rs.rows.into_iter()
-            .map(move |r| Ok(sqlx::Either::Right(SpinPgRow { columns: columns.clone(), inner: r })));
-        Box::pin(futures::stream::iter(rows))
+        }
+
+        let statements = statements(&sql, Dialect::Postgres).into_iter().map(str::to_owned).collect::<Vec<_>>();
+        if statements.len() > 1 && !args.inner.is_empty() {
+            return Box::pin(futures::stream::once(async move { Err(arguments_for_several_statements()) }));
+        }
+
+        // Spin runs one statement per call.  The statements run as the stream is
+        // read, and the first to fail ends it, as in a script.
+        let steps = futures::stream::iter(statements)
+            .map(move |sql| self.statement_steps(&sql, &args))
+            .scan(false, |failed, steps| {
+                if *failed {
+                    return ready(None);
+                }
+                *failed = steps.is_err();
+                ready(Some(steps))
+            })
+            .map_ok(|steps| futures::stream::iter(steps.into_iter().map(Ok)))
+            .try_flatten();
+        Box::pin(steps)
     }
 
     fn execute<'e, 'q: 'e, E: 'q>(
@@ -314,13 +499,20 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
             E: sqlx::Execute<'q, Self::Database>,
     {
         tracing::debug!("EXECing {}", query.sql());
+        if statements(query.sql(), Dialect::Postgres).len() > 1 {
+            return Box::pin(self.fetch_many(query).try_filter_map(|step| ready(Ok(step.left()))).try_collect());
+        }
         let args = match query.take_arguments() {
             Ok(a) => a.unwrap_or_default(),
             Err(e) => {
                 return Box::pin(async move { Err(sqlx::Error::Encode(e)) });
             }
         };
-        let count = match self.0.execute(query.sql(), args.as_slice()).as_sqlx_result() {
+        let sql = self.sql(query.sql());
+        if let Err(e) = check_arguments(&sql, Dialect::Postgres, args.inner.len()) {
+            return Box::pin(async move { Err(e) });
+        }
+        let count = match self.inner.execute(&sql, args.as_slice()).as_sqlx_result() {
             Ok(count) => count,
             Err(e) => {
                 return Box::pin(async move { Err(e) });
@@ -341,23 +533,28 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
         E: sqlx::Execute<'q, Self::Database>
     {
         tracing::debug!("FETCH-OPTIONALing {}", query.sql());
+        if statements(query.sql(), Dialect::Postgres).len() > 1 {
+            let mut rows = self.fetch_many(query).try_filter_map(|step| ready(Ok(step.right())));
+            return Box::pin(async move { rows.try_next().await });
+        }
         let args = match query.take_arguments() {
             Ok(a) => a.unwrap_or_default(),
             Err(e) => {
                 return Box::pin(async move { Err(sqlx::Error::Encode(e)) });
             }
         };
-        let rs = match self.0.query(query.sql(), args.as_slice()).as_sqlx_result() {
+        let sql = self.sql(query.sql());
+        if let Err(e) = check_arguments(&sql, Dialect::Postgres, args.inner.len()) {
+            return Box::pin(async move { Err(e) });
+        }
+        let (columns, rows) = match self.query_rows(&sql, &args) {
             Ok(rs) => rs,
             Err(e) => {
                 return Box::pin(async move { Err(e) });
             }
         };
 
-        // TODO: deduplicate
-        let columns_core = rs.columns.iter().enumerate().map(|(i, c)| SpinPgColumn::new(i, c)).collect::<Vec<_>>();
-        let columns = std::sync::Arc::new(columns_core);
-        let row = rs.rows.into_iter()
+        let row = rows.into_iter()
             .map(move |r| SpinPgRow { columns: columns.clone(), inner: r })
             .next();
 
@@ -385,3 +582,74 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
         todo!("Spin does not currently support sqlx database describe for offline macros")
     }
 }
+
+// Generic sqlx code often takes `&mut conn` or `&mut *tx`, so these just pass
+// the query on to the shared reference impl.
+impl<'c> sqlx::Executor<'c> for &'c mut Connection {
+    type Database = Connection;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<
+        'e,
+        Result<
+            sqlx::Either<<Self::Database as sqlx::Database>::QueryResult, <Self::Database as sqlx::Database>::Row>,
+            sqlx::Error,
+        >,
+    >
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>
+    {
+        let conn: &'c Connection = self;
+        conn.fetch_many(query)
+    }
+
+    fn execute<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<<Self::Database as sqlx::Database>::QueryResult, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>,
+    {
+        let conn: &'c Connection = self;
+        conn.execute(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<<Self::Database as sqlx::Database>::Row>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>
+    {
+        let conn: &'c Connection = self;
+        conn.fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [<Self::Database as sqlx::Database>::TypeInfo],
+    ) -> BoxFuture<'e, Result<<Self::Database as sqlx::Database>::Statement<'q>, sqlx::Error>>
+    where
+        'c: 'e
+    {
+        let conn: &'c Connection = self;
+        conn.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<sqlx::Describe<Self::Database>, sqlx::Error>>
+    where
+        'c: 'e
+    {
+        let conn: &'c Connection = self;
+        conn.describe(sql)
+    }
+}
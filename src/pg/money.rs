@@ -0,0 +1,120 @@
+// Spin's Postgres interface doesn't pass money values natively, so they
+// travel as text: cast parameters in SQL (`VALUES ($1::text::money)`) and
+// select columns as text (`SELECT price::text FROM products`).
+
+use std::fmt::Display;
+
+use super::convert::text_of;
+use super::{Connection, SpinPgTypeInfo};
+
+/// A Postgres `MONEY` value, in the currency's minor units (e.g. cents).
+///
+/// This assumes the database's `lc_monetary` uses two decimal places.
+/// Currency symbols and thousands separators are ignored when decoding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpinPgMoney(pub i64);
+
+#[derive(Debug)]
+struct BadMoneyError(String);
+impl std::error::Error for BadMoneyError {}
+impl Display for BadMoneyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad money value: '{}'", self.0)
+    }
+}
+
+impl SpinPgMoney {
+    pub fn parse(text: &str) -> Result<Self, sqlx::error::BoxDynError> {
+        let bad = || -> sqlx::error::BoxDynError { Box::new(BadMoneyError(text.to_owned())) };
+
+        let trimmed = text.trim();
+        let negative = trimmed.contains('-') || (trimmed.starts_with('(') && trimmed.ends_with(')'));
+
+        let mut whole = String::new();
+        let mut frac: Option<String> = None;
+        for c in trimmed.chars() {
+            match c {
+                '0'..='9' => match frac.as_mut() {
+                    Some(f) => f.push(c),
+                    None => whole.push(c),
+                },
+                '.' if frac.is_none() => frac = Some(String::new()),
+                '.' => return Err(bad()),
+                _ => (),
+            }
+        }
+
+        let frac = frac.unwrap_or_default();
+        if whole.is_empty() && frac.is_empty() || frac.len() > 2 {
+            return Err(bad());
+        }
+
+        let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| bad())? };
+        let frac: i64 = if frac.is_empty() { 0 } else { format!("{frac:0<2}").parse().map_err(|_| bad())? };
+        let minor = whole.checked_mul(100).and_then(|w| w.checked_add(frac)).ok_or_else(bad)?;
+
+        Ok(Self(if negative { -minor } else { minor }))
+    }
+}
+
+impl Display for SpinPgMoney {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{sign}{}.{:02}", abs / 100, abs % 100)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Connection> for SpinPgMoney {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(self.to_string()));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+impl<'r> sqlx::Decode<'r, Connection> for SpinPgMoney {
+    fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Self::parse(&text_of(value, "money")?)
+    }
+}
+impl sqlx::Type<Connection> for SpinPgMoney {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        SpinPgTypeInfo::Money
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Money | SpinPgTypeInfo::Str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_money_text() {
+        assert_eq!(SpinPgMoney(123456), SpinPgMoney::parse("$1,234.56").unwrap());
+        assert_eq!(SpinPgMoney(-123456), SpinPgMoney::parse("-$1,234.56").unwrap());
+        assert_eq!(SpinPgMoney(-1050), SpinPgMoney::parse("($10.50)").unwrap());
+        assert_eq!(SpinPgMoney(1050), SpinPgMoney::parse("10.5").unwrap());
+        assert_eq!(SpinPgMoney(1000), SpinPgMoney::parse("£10").unwrap());
+        assert_eq!(SpinPgMoney(5), SpinPgMoney::parse(".05").unwrap());
+    }
+
+    #[test]
+    fn parse_rejects_bad_money() {
+        assert!(SpinPgMoney::parse("").is_err());
+        assert!(SpinPgMoney::parse("$").is_err());
+        assert!(SpinPgMoney::parse("1.234").is_err());
+        assert!(SpinPgMoney::parse("1.2.3").is_err());
+        assert!(SpinPgMoney::parse("92233720368547758.08").is_err());
+        assert!(SpinPgMoney::parse("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for value in [0, 5, -5, 123456, -123456, i64::MAX, i64::MIN + 1] {
+            let money = SpinPgMoney(value);
+            assert_eq!(money, SpinPgMoney::parse(&money.to_string()).unwrap());
+        }
+        assert_eq!("-0.05", SpinPgMoney(-5).to_string());
+    }
+}
@@ -0,0 +1,128 @@
+// Spin's Postgres interface doesn't pass network types natively, so they
+// travel as text: cast parameters in SQL (`VALUES ($1::text::inet)`) and
+// select columns as text (`SELECT client_ip::text FROM audit_log`).
+
+use std::net::IpAddr;
+
+use super::convert::text_of;
+use super::{Connection, SpinPgTypeInfo};
+
+impl<'q> sqlx::Encode<'q, Connection> for IpAddr {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(self.to_string()));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+impl<'r> sqlx::Decode<'r, Connection> for IpAddr {
+    fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let text = text_of(value, "inet")?;
+        // `inet::text` always includes the netmask, but a single host has a full-length one
+        let addr = match text.split_once('/') {
+            Some((addr, "32")) if !addr.contains(':') => addr,
+            Some((addr, "128")) if addr.contains(':') => addr,
+            Some(_) => return Err(format!("'{text}' is a network, not a single address").into()),
+            None => &text,
+        };
+        Ok(addr.parse()?)
+    }
+}
+impl sqlx::Type<Connection> for IpAddr {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        SpinPgTypeInfo::Inet
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Inet | SpinPgTypeInfo::Cidr | SpinPgTypeInfo::Str)
+    }
+}
+
+#[cfg(feature = "ipnetwork")]
+impl<'q> sqlx::Encode<'q, Connection> for ipnetwork::IpNetwork {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(self.to_string()));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+#[cfg(feature = "ipnetwork")]
+impl<'r> sqlx::Decode<'r, Connection> for ipnetwork::IpNetwork {
+    fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(text_of(value, "inet")?.parse()?)
+    }
+}
+#[cfg(feature = "ipnetwork")]
+impl sqlx::Type<Connection> for ipnetwork::IpNetwork {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        SpinPgTypeInfo::Inet
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::Inet | SpinPgTypeInfo::Cidr | SpinPgTypeInfo::Str)
+    }
+}
+
+#[cfg(feature = "mac_address")]
+impl<'q> sqlx::Encode<'q, Connection> for mac_address::MacAddress {
+    fn encode_by_ref(&self, buf: &mut <Connection as sqlx::Database>::ArgumentBuffer<'q>) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        buf.push(spin_sdk::pg::ParameterValue::Str(self.to_string()));
+        Ok(sqlx::encode::IsNull::No)
+    }
+}
+#[cfg(feature = "mac_address")]
+impl<'r> sqlx::Decode<'r, Connection> for mac_address::MacAddress {
+    fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(text_of(value, "macaddr")?.parse()?)
+    }
+}
+#[cfg(feature = "mac_address")]
+impl sqlx::Type<Connection> for mac_address::MacAddress {
+    fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
+        SpinPgTypeInfo::MacAddr
+    }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        matches!(ty, SpinPgTypeInfo::MacAddr | SpinPgTypeInfo::Str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use spin_sdk::pg::DbValue;
+
+    use super::*;
+    use crate::pg::SpinPgValue;
+
+    fn decode(text: &str) -> Result<IpAddr, sqlx::error::BoxDynError> {
+        <IpAddr as sqlx::Decode<Connection>>::decode(SpinPgValue::from(DbValue::Str(text.to_owned())))
+    }
+
+    #[test]
+    fn decode_host_addresses() {
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)), decode("192.168.0.1").unwrap());
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)), decode("192.168.0.1/32").unwrap());
+        assert_eq!(IpAddr::V6(Ipv6Addr::LOCALHOST), decode("::1").unwrap());
+        assert_eq!(IpAddr::V6(Ipv6Addr::LOCALHOST), decode("::1/128").unwrap());
+        assert_eq!("2001:db8::ff00:42:8329".parse::<IpAddr>().unwrap(), decode("2001:db8::ff00:42:8329/128").unwrap());
+    }
+
+    #[test]
+    fn decode_rejects_networks() {
+        for text in ["10.0.0.0/8", "10.0.0.1/31", "2001:db8::/32", "::1/127", "10.0.0.1/128", "::1/32"] {
+            let e = decode(text).unwrap_err();
+            assert_eq!(format!("'{text}' is a network, not a single address"), e.to_string());
+        }
+    }
+
+    #[test]
+    fn decode_rejects_bad_addresses() {
+        for text in ["", "localhost", "10.0.0.256", "10.0.0.1/", "::g"] {
+            assert!(decode(text).is_err(), "{text}");
+        }
+        assert!(<IpAddr as sqlx::Decode<Connection>>::decode(SpinPgValue::from(DbValue::Int32(1))).is_err());
+    }
+
+    #[test]
+    fn encode_as_text() {
+        let mut buf = vec![];
+        let _ = sqlx::Encode::<Connection>::encode_by_ref(&IpAddr::V6(Ipv6Addr::LOCALHOST), &mut buf).unwrap();
+        assert!(matches!(&buf[..], [spin_sdk::pg::ParameterValue::Str(s)] if s == "::1"));
+    }
+}
@@ -16,11 +16,35 @@ pub struct SpinPgQueryResult {
 pub struct SpinPgColumn {
     index: usize,
     column: spin_sdk::pg::Column,
+    type_info: SpinPgTypeInfo,
 }
 
 impl SpinPgColumn {
     pub(crate) fn new(index: usize, column: &spin_sdk::pg::Column) -> Self {
-        Self { index, column: column.clone() }
+        let type_info = match column.data_type {
+            spin_sdk::pg::DbDataType::Boolean => SpinPgTypeInfo::Bool,
+            spin_sdk::pg::DbDataType::Int16 => SpinPgTypeInfo::Int16,
+            spin_sdk::pg::DbDataType::Int32 => SpinPgTypeInfo::Int32,
+            spin_sdk::pg::DbDataType::Int64 => SpinPgTypeInfo::Int64,
+            spin_sdk::pg::DbDataType::Floating32 => SpinPgTypeInfo::Floating32,
+            spin_sdk::pg::DbDataType::Floating64 => SpinPgTypeInfo::Floating64,
+            spin_sdk::pg::DbDataType::Str => SpinPgTypeInfo::Str,
+            spin_sdk::pg::DbDataType::Binary => SpinPgTypeInfo::Binary,
+            _ => SpinPgTypeInfo::Unsupported,
+        };
+        Self { index, column: column.clone(), type_info }
+    }
+}
+
+#[cfg(test)]
+impl SpinPgRow {
+    /// A row of the given columns, with their types, and values, for tests.
+    pub(crate) fn for_test(values: Vec<(&str, spin_sdk::pg::DbDataType, spin_sdk::pg::DbValue)>) -> Self {
+        let columns = values.iter().enumerate()
+            .map(|(i, (name, data_type, _))| SpinPgColumn::new(i, &spin_sdk::pg::Column { name: name.to_string(), data_type: *data_type }))
+            .collect();
+        let values = values.into_iter().map(|(_, _, value)| value).collect();
+        Self { columns: std::sync::Arc::new(columns), inner: values }
     }
 }
 
@@ -44,14 +68,52 @@ impl sqlx::Row for SpinPgRow {
         }
 
         let val = &self.inner[uindex];
+        if let spin_sdk::pg::DbValue::Unsupported = val {
+            // Don't let this pass as NULL, or `Option` fields would silently come back `None`
+            return Err(super::error::unsupported_column(&self.columns[uindex].column));
+        }
         Ok(SpinPgValue { inner: val.clone() })
     }
 }
 
+impl SpinPgRow {
+    /// The row as Spin returned it.
+    pub fn as_inner(&self) -> &spin_sdk::pg::Row {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> spin_sdk::pg::Row {
+        self.inner
+    }
+}
+
+impl From<SpinPgRow> for spin_sdk::pg::Row {
+    fn from(row: SpinPgRow) -> Self {
+        row.inner
+    }
+}
+
 impl SpinPgQueryResult {
     pub fn count(&self) -> u64 {
         self.count
     }
+
+    /// Spin's result for a statement is just its row count.
+    pub fn into_inner(self) -> u64 {
+        self.count
+    }
+}
+
+impl From<u64> for SpinPgQueryResult {
+    fn from(count: u64) -> Self {
+        Self { count }
+    }
+}
+
+impl From<SpinPgQueryResult> for u64 {
+    fn from(result: SpinPgQueryResult) -> Self {
+        result.count
+    }
 }
 
 impl Extend<SpinPgQueryResult> for SpinPgQueryResult {
@@ -74,7 +136,7 @@ impl sqlx::Column for SpinPgColumn {
     }
 
     fn type_info(&self) -> &<Self::Database as sqlx::Database>::TypeInfo {
-        todo!()
+        &self.type_info
     }
 }
 
@@ -83,6 +145,28 @@ pub struct SpinPgValue {
     pub(crate) inner: spin_sdk::pg::DbValue,
 }
 
+impl SpinPgValue {
+    pub fn as_inner(&self) -> &spin_sdk::pg::DbValue {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> spin_sdk::pg::DbValue {
+        self.inner
+    }
+}
+
+impl From<spin_sdk::pg::DbValue> for SpinPgValue {
+    fn from(inner: spin_sdk::pg::DbValue) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<SpinPgValue> for spin_sdk::pg::DbValue {
+    fn from(value: SpinPgValue) -> Self {
+        value.inner
+    }
+}
+
 impl<'q> sqlx::ValueRef<'q> for SpinPgValue {
     type Database = Connection;
 
@@ -106,7 +190,7 @@ impl<'q> sqlx::ValueRef<'q> for SpinPgValue {
             spin_sdk::pg::DbValue::Floating64(_) => SpinPgTypeInfo::Floating64,
             spin_sdk::pg::DbValue::Str(_) => SpinPgTypeInfo::Str,
             spin_sdk::pg::DbValue::Binary(_) => SpinPgTypeInfo::Binary,
-            spin_sdk::pg::DbValue::Unsupported => SpinPgTypeInfo::Null,
+            spin_sdk::pg::DbValue::Unsupported => SpinPgTypeInfo::Unsupported,
         };
         std::borrow::Cow::Owned(type_info)
     }
@@ -120,15 +204,15 @@ impl sqlx::Value for SpinPgValue {
     type Database = Connection;
 
     fn as_ref(&self) -> <Self::Database as sqlx::Database>::ValueRef<'_> {
-        todo!()
+        self.clone()
     }
 
     fn type_info(&self) -> std::borrow::Cow<'_, <Self::Database as sqlx::Database>::TypeInfo> {
-        todo!()
+        sqlx::ValueRef::type_info(self)
     }
 
     fn is_null(&self) -> bool {
-        todo!()
+        sqlx::ValueRef::is_null(self)
     }
 }
 
@@ -10,10 +10,36 @@ pub enum SpinPgTypeInfo {
     Floating64,
     Str,
     Binary,
+    BoolArray,
+    Int16Array,
+    Int32Array,
+    Int64Array,
+    Floating32Array,
+    Floating64Array,
+    StrArray,
+    Inet,
+    Cidr,
+    MacAddr,
+    Interval,
+    Money,
+    /// A type not built into Postgres, such as an enum or domain, identified by name.
+    Custom(String),
     Null,
     Unsupported,
 }
 
+impl SpinPgTypeInfo {
+    /// The type info for a custom type (such as an enum created with `CREATE TYPE`).
+    pub fn with_name(name: &str) -> Self {
+        Self::Custom(name.to_owned())
+    }
+
+    /// The type info for an array of a custom type.
+    pub fn array_of(name: &str) -> Self {
+        Self::Custom(format!("{name}[]"))
+    }
+}
+
 impl Display for SpinPgTypeInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use sqlx::TypeInfo;
@@ -36,6 +62,19 @@ impl sqlx::TypeInfo for SpinPgTypeInfo {
             Self::Floating64 => "double precision",
             Self::Str => "text",
             Self::Binary => "bytea",
+            Self::BoolArray => "boolean[]",
+            Self::Int16Array => "smallint[]",
+            Self::Int32Array => "int[]",
+            Self::Int64Array => "bigint[]",
+            Self::Floating32Array => "real[]",
+            Self::Floating64Array => "double precision[]",
+            Self::StrArray => "text[]",
+            Self::Inet => "inet",
+            Self::Cidr => "cidr",
+            Self::MacAddr => "macaddr",
+            Self::Interval => "interval",
+            Self::Money => "money",
+            Self::Custom(name) => name,
             Self::Null => "NULL",
             Self::Unsupported => "<unsupported>",
         }
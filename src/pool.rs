@@ -0,0 +1,172 @@
+// What the SQLite and Postgres pools share: a slot of connections for each
+// distinct set of connection options, kept for as long as the component
+// instance lives.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub(crate) trait Pooled: Sized {
+    /// Whether the connection can be handed out again once given back.  One
+    /// left mid-transaction (say, by `mem::forget`ting a `Transaction`) can't.
+    fn reusable(&self) -> bool;
+}
+
+#[derive(Debug)]
+pub(crate) struct Connections<C> {
+    shared: OnceLock<C>,
+    // Connections given back by `Lease`s, to hand out again
+    idle: Mutex<Vec<C>>,
+}
+
+impl<C> Default for Connections<C> {
+    fn default() -> Self {
+        Self { shared: OnceLock::new(), idle: Mutex::new(vec![]) }
+    }
+}
+
+pub(crate) type Slot<C> = Arc<Connections<C>>;
+
+// Pools share a slot only if all their options match, so that one pool's
+// pragmas or settings never apply to another's queries.
+pub(crate) struct Slots<O, C> {
+    slots: OnceLock<Mutex<HashMap<O, Slot<C>>>>,
+}
+
+impl<O: Clone + Eq + Hash, C> Slots<O, C> {
+    pub(crate) const fn new() -> Self {
+        Self { slots: OnceLock::new() }
+    }
+
+    pub(crate) fn get(&self, options: &O) -> Slot<C> {
+        let mut slots = self.slots.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        slots.entry(options.clone()).or_default().clone()
+    }
+}
+
+impl<C: Pooled> Connections<C> {
+    /// The slot's shared connection, opening it if this is the first use.
+    pub(crate) fn shared(&self, open: impl FnOnce() -> Result<C, sqlx::Error>) -> Result<&C, sqlx::Error> {
+        if let Some(conn) = self.shared.get() {
+            return Ok(conn);
+        }
+        let conn = open()?;
+        Ok(self.shared.get_or_init(|| conn))
+    }
+
+    /// An idle connection, or a newly opened one, for exclusive use.
+    pub(crate) fn lease(self: &Arc<Self>, open: impl FnOnce() -> Result<C, sqlx::Error>) -> Result<Lease<C>, sqlx::Error> {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => open()?,
+        };
+        Ok(Lease { conn: Some(conn), slot: self.clone() })
+    }
+}
+
+/// A connection which goes back to its slot's idle list when dropped.
+#[derive(Debug)]
+pub(crate) struct Lease<C: Pooled> {
+    conn: Option<C>,
+    slot: Slot<C>,
+}
+
+impl<C: Pooled> Deref for Lease<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.conn.as_ref().expect("connection already returned to the pool")
+    }
+}
+
+impl<C: Pooled> DerefMut for Lease<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.conn.as_mut().expect("connection already returned to the pool")
+    }
+}
+
+impl<C: Pooled> Drop for Lease<C> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take().filter(C::reusable) {
+            self.slot.idle.lock().unwrap_or_else(|e| e.into_inner()).push(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct FakeConnection {
+        id: usize,
+        in_transaction: bool,
+    }
+
+    impl Pooled for FakeConnection {
+        fn reusable(&self) -> bool {
+            !self.in_transaction
+        }
+    }
+
+    fn opener(id: usize) -> impl FnOnce() -> Result<FakeConnection, sqlx::Error> {
+        move || Ok(FakeConnection { id, in_transaction: false })
+    }
+
+    #[test]
+    fn slots_are_shared_only_by_equal_options() {
+        static SLOTS: Slots<(&str, bool), FakeConnection> = Slots::new();
+        assert!(Arc::ptr_eq(&SLOTS.get(&("a", true)), &SLOTS.get(&("a", true))));
+        assert!(!Arc::ptr_eq(&SLOTS.get(&("a", true)), &SLOTS.get(&("a", false))));
+        assert!(!Arc::ptr_eq(&SLOTS.get(&("a", true)), &SLOTS.get(&("b", true))));
+    }
+
+    #[test]
+    fn shared_connection_is_opened_once() {
+        let slot = Slot::<FakeConnection>::default();
+        assert_eq!(1, slot.shared(opener(1)).unwrap().id);
+        assert_eq!(1, slot.shared(opener(2)).unwrap().id);
+        assert!(slot.shared(|| Err(sqlx::Error::PoolClosed)).is_ok());
+    }
+
+    #[test]
+    fn failing_to_open_leaves_the_slot_empty() {
+        let slot = Slot::<FakeConnection>::default();
+        assert!(slot.shared(|| Err(sqlx::Error::PoolClosed)).is_err());
+        assert_eq!(2, slot.shared(opener(2)).unwrap().id);
+    }
+
+    #[test]
+    fn leases_are_reused_once_dropped() {
+        let slot = Slot::<FakeConnection>::default();
+        let first = slot.lease(opener(1)).unwrap();
+        let second = slot.lease(opener(2)).unwrap();
+        assert_eq!((1, 2), (first.id, second.id));
+        drop(first);
+        assert_eq!(1, slot.lease(opener(3)).unwrap().id);
+        // Never the shared connection
+        assert_eq!(4, slot.shared(opener(4)).unwrap().id);
+    }
+
+    #[test]
+    fn connections_are_reused_once_their_transaction_ends() {
+        let slot = Slot::<FakeConnection>::default();
+        let mut lease = slot.lease(opener(1)).unwrap();
+        // As `begin()` and then `commit()` or `rollback()` on `&mut *lease`
+        lease.in_transaction = true;
+        lease.in_transaction = false;
+        drop(lease);
+        assert_eq!(1, slot.lease(opener(2)).unwrap().id);
+    }
+
+    #[test]
+    fn connections_left_in_a_transaction_are_not_reused() {
+        let slot = Slot::<FakeConnection>::default();
+        let mut lease = slot.lease(opener(1)).unwrap();
+        lease.in_transaction = true;
+        drop(lease);
+        assert_eq!(2, slot.lease(opener(2)).unwrap().id);
+    }
+}
@@ -0,0 +1,618 @@
+// Just enough SQL lexing to find the parts of a query that are code, as opposed
+// to string literals, quoted identifiers and comments.  This is not a parser!
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TokenKind {
+    /// Anything that isn't quoted or a comment
+    Code,
+    /// A string literal or quoted identifier, including its quotes
+    Quoted,
+    Comment,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// The byte offset of the token in the SQL
+    pub start: usize,
+}
+
+pub(crate) fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+pub(crate) fn tokenize(sql: &str, dialect: Dialect) -> Vec<Token<'_>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = vec![];
+    let mut code_start = 0;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let end = match (bytes[pos], dialect) {
+            (b'\'', Dialect::Postgres) if is_escape_string_prefix(sql, pos) => {
+                Some((TokenKind::Quoted, pos - 1, skip_quoted(bytes, pos, b'\'', true)))
+            },
+            (b'\'', _) | (b'"', _) => {
+                Some((TokenKind::Quoted, pos, skip_quoted(bytes, pos, bytes[pos], false)))
+            },
+            (b'`', Dialect::Sqlite) => Some((TokenKind::Quoted, pos, skip_quoted(bytes, pos, b'`', false))),
+            (b'[', Dialect::Sqlite) => Some((TokenKind::Quoted, pos, skip_quoted(bytes, pos, b']', false))),
+            (b'$', Dialect::Postgres) => {
+                dollar_quote_tag(sql, pos).map(|tag| (TokenKind::Quoted, pos, skip_dollar_quoted(sql, pos, tag)))
+            },
+            (b'-', _) if bytes.get(pos + 1) == Some(&b'-') => {
+                let end = sql[pos..].find('\n').map(|n| pos + n + 1).unwrap_or(bytes.len());
+                Some((TokenKind::Comment, pos, end))
+            },
+            (b'/', _) if bytes.get(pos + 1) == Some(&b'*') => {
+                Some((TokenKind::Comment, pos, skip_block_comment(bytes, pos, dialect == Dialect::Postgres)))
+            },
+            _ => None,
+        };
+
+        match end {
+            Some((kind, start, end)) => {
+                if start > code_start {
+                    tokens.push(Token { kind: TokenKind::Code, text: &sql[code_start..start], start: code_start });
+                }
+                tokens.push(Token { kind, text: &sql[start..end], start });
+                pos = end;
+                code_start = end;
+            },
+            None => pos += 1,
+        }
+    }
+
+    if code_start < bytes.len() {
+        tokens.push(Token { kind: TokenKind::Code, text: &sql[code_start..], start: code_start });
+    }
+
+    tokens
+}
+
+// Postgres `E'...'` strings allow backslash escapes
+fn is_escape_string_prefix(sql: &str, quote_pos: usize) -> bool {
+    let before = &sql[..quote_pos];
+    let mut chars = before.chars().rev();
+    matches!(chars.next(), Some('E') | Some('e')) && !chars.next().is_some_and(is_ident_char)
+}
+
+// Returns the end of a quoted section starting at `start`.  Doubling the closing
+// quote escapes it.  If the quote is unterminated, returns the end of the SQL,
+// and leaves it to the database to complain.
+fn skip_quoted(bytes: &[u8], start: usize, close: u8, backslash_escapes: bool) -> usize {
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        if backslash_escapes && bytes[pos] == b'\\' {
+            pos += 2;
+            continue;
+        }
+        if bytes[pos] == close {
+            if bytes.get(pos + 1) == Some(&close) && close != b']' {
+                pos += 2;
+                continue;
+            }
+            return pos + 1;
+        }
+        pos += 1;
+    }
+    bytes.len()
+}
+
+fn dollar_quote_tag(sql: &str, start: usize) -> Option<&str> {
+    if sql[..start].chars().next_back().is_some_and(is_ident_char) {
+        return None;  // `$` can appear within identifiers
+    }
+    let rest = &sql[start + 1..];
+    let tag_len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    let tag = &rest[..tag_len];
+    if tag.starts_with(|c: char| c.is_ascii_digit()) || !rest[tag_len..].starts_with('$') {
+        return None;  // `$1` is a parameter, not a quote
+    }
+    Some(&sql[start..start + tag_len + 2])
+}
+
+fn skip_dollar_quoted(sql: &str, start: usize, tag: &str) -> usize {
+    let body_start = start + tag.len();
+    match sql[body_start..].find(tag) {
+        Some(n) => body_start + n + tag.len(),
+        None => sql.len(),
+    }
+}
+
+fn skip_block_comment(bytes: &[u8], start: usize, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut pos = start;
+    while pos + 1 < bytes.len() {
+        if bytes[pos] == b'/' && bytes[pos + 1] == b'*' && (nested || depth == 0) {
+            depth += 1;
+            pos += 2;
+        } else if bytes[pos] == b'*' && bytes[pos + 1] == b'/' {
+            depth -= 1;
+            pos += 2;
+            if depth == 0 {
+                return pos;
+            }
+        } else {
+            pos += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// The words (keywords and unquoted identifiers) in the code parts of the SQL.
+pub(crate) fn words(sql: &str, dialect: Dialect) -> impl Iterator<Item = &str> {
+    tokenize(sql, dialect)
+        .into_iter()
+        .filter(|token| token.kind == TokenKind::Code)
+        .flat_map(|token| token.text.split(|c: char| !is_ident_char(c)))
+        .filter(|word| !word.is_empty())
+}
+
+/// Splits SQL into its statements, at semicolons which are not quoted, in
+/// comments, or within the `BEGIN ... END` body of a trigger or function.
+/// Statements which are empty or contain only comments are omitted.
+pub(crate) fn split_statements(sql: &str, dialect: Dialect) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut splitter = Splitter::default();
+    let mut statement_start = 0;
+
+    for token in tokenize(sql, dialect) {
+        match token.kind {
+            TokenKind::Comment => (),
+            TokenKind::Quoted => splitter.has_content = true,
+            TokenKind::Code => {
+                let mut word_start = None;
+                for (i, c) in token.text.char_indices().chain(std::iter::once((token.text.len(), ' '))) {
+                    if is_ident_char(c) {
+                        word_start.get_or_insert(i);
+                        continue;
+                    }
+                    if let Some(ws) = word_start.take() {
+                        splitter.word(&token.text[ws..i]);
+                    }
+                    if c == ';' && splitter.block_depth == 0 {
+                        let end = token.start + i;
+                        if splitter.has_content {
+                            statements.push(sql[statement_start..end].trim());
+                        }
+                        statement_start = end + 1;
+                        splitter = Splitter::default();
+                    } else if !c.is_whitespace() {
+                        splitter.has_content = true;
+                    }
+                }
+            },
+        }
+    }
+
+    if splitter.has_content {
+        statements.push(sql[statement_start..].trim());
+    }
+
+    statements
+}
+
+#[derive(Default)]
+struct Splitter {
+    has_content: bool,
+    word_count: usize,
+    is_create: bool,
+    has_body: bool,
+    block_depth: usize,
+}
+
+impl Splitter {
+    fn word(&mut self, word: &str) {
+        self.has_content = true;
+        self.word_count += 1;
+
+        if self.word_count == 1 {
+            self.is_create = word.eq_ignore_ascii_case("CREATE");
+        } else if self.is_create && !self.has_body {
+            self.has_body = ["TRIGGER", "FUNCTION", "PROCEDURE"].iter().any(|w| word.eq_ignore_ascii_case(w));
+        } else if self.has_body {
+            if word.eq_ignore_ascii_case("BEGIN") || word.eq_ignore_ascii_case("CASE") {
+                self.block_depth += 1;
+            } else if word.eq_ignore_ascii_case("END") {
+                self.block_depth = self.block_depth.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// The statements to run, one at a time, for the SQL of a query.  A single
+/// statement is passed on exactly as written.
+pub(crate) fn statements(sql: &str, dialect: Dialect) -> Vec<&str> {
+    let statements = split_statements(sql, dialect);
+    if statements.len() > 1 {
+        statements
+    } else {
+        vec![sql]
+    }
+}
+
+pub(crate) fn arguments_for_several_statements() -> sqlx::Error {
+    sqlx::Error::Protocol("can't bind arguments to SQL containing several statements: run them one at a time".into())
+}
+
+const CHANGES_ROWS: &[&str] = &["INSERT", "UPDATE", "DELETE", "MERGE", "REPLACE"];
+
+/// Whether the statement is one that changes rows, and so has a row count.
+/// Spin doesn't say how many rows a statement affected when it returns rows.
+pub(crate) fn counts_rows(sql: &str, dialect: Dialect) -> bool {
+    words(sql, dialect).next().is_some_and(|word| CHANGES_ROWS.iter().any(|w| word.eq_ignore_ascii_case(w)))
+}
+
+/// If the SQL is a single query which only reads rows, that query, without
+/// any trailing semicolon, for fetching a page at a time.
+pub(crate) fn pageable_query(sql: &str, dialect: Dialect) -> Option<&str> {
+    let [query] = split_statements(sql, dialect)[..] else {
+        return None;
+    };
+    let words = words(query, dialect).collect::<Vec<_>>();
+    let is = |word: &str, keywords: &[&str]| keywords.iter().any(|w| word.eq_ignore_ascii_case(w));
+
+    let reads_rows = match words.first() {
+        Some(word) if is(word, &["SELECT", "VALUES"]) => true,
+        // Common table expressions may change rows
+        Some(word) if is(word, &["WITH"]) => !words.iter().any(|word| is(word, CHANGES_ROWS)),
+        _ => false,
+    };
+    // A query which limits its own rows would have its limit paged, rather than
+    // its rows, and Postgres doesn't allow locking rows in the subquery paging
+    // wraps the query in
+    let limits_rows = words.iter().any(|word| is(word, &["LIMIT", "OFFSET", "FETCH"]));
+    let locks_rows = words.windows(2).any(|pair| is(pair[0], &["FOR"]) && is(pair[1], &["UPDATE", "SHARE", "NO", "KEY"]));
+
+    (reads_rows && !limits_rows && !locks_rows).then_some(query)
+}
+
+/// The SQL for one page of a query's rows.  The query goes on lines of its
+/// own so that a trailing line comment can't swallow the paging.
+pub(crate) fn page_sql(query: &str, page_size: usize, offset: usize) -> String {
+    format!("SELECT * FROM (\n{query}\n) AS spin_sqlx_page LIMIT {page_size} OFFSET {offset}")
+}
+
+/// A query parameter placeholder.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Placeholder<'a> {
+    /// SQLite's `?`, which takes the number after the highest so far
+    Next,
+    /// SQLite's `?N` or Postgres's `$N`
+    Numbered(usize),
+    /// SQLite's `:name`, `@name` or `$name`, including the prefix
+    Named(&'a str),
+}
+
+/// The placeholders in the code parts of the SQL, with their byte ranges.
+pub(crate) fn placeholders(sql: &str, dialect: Dialect) -> Vec<(std::ops::Range<usize>, Placeholder<'_>)> {
+    let mut found = vec![];
+
+    for token in tokenize(sql, dialect).into_iter().filter(|token| token.kind == TokenKind::Code) {
+        let text = token.text;
+        for (pos, c) in text.char_indices() {
+            let rest = &text[pos + 1..];
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let after_ident = text[..pos].chars().next_back().is_some_and(is_ident_char);
+
+            let placeholder = match (c, dialect) {
+                ('?', Dialect::Sqlite) if digits == 0 => Some((1, Placeholder::Next)),
+                ('?', Dialect::Sqlite) => rest[..digits].parse().ok().map(|n| (1 + digits, Placeholder::Numbered(n))),
+                (':' | '@' | '$', Dialect::Sqlite) if !after_ident => {
+                    let name_len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                    (name_len > 0).then(|| (1 + name_len, Placeholder::Named(&text[pos..pos + 1 + name_len])))
+                },
+                ('$', Dialect::Postgres) if !after_ident && digits > 0 && !rest[digits..].starts_with(is_ident_char) => {
+                    rest[..digits].parse().ok().map(|n| (1 + digits, Placeholder::Numbered(n)))
+                },
+                _ => None,
+            };
+
+            if let Some((len, placeholder)) = placeholder {
+                let start = token.start + pos;
+                found.push((start..start + len, placeholder));
+            }
+        }
+    }
+
+    found
+}
+
+/// Rewrites placeholders written for the other backend into the dialect's
+/// own: SQLite's `?` and `?N` become `$N` for Postgres, and Postgres's `$N`
+/// become `?N` for SQLite.  Placeholders in string literals, quoted identifiers
+/// and comments are left alone.
+pub(crate) fn translate_placeholders(sql: &str, dialect: Dialect) -> std::borrow::Cow<'_, str> {
+    let replacements = match dialect {
+        Dialect::Sqlite => placeholders(sql, dialect)
+            .into_iter()
+            .filter_map(|(range, placeholder)| match placeholder {
+                Placeholder::Named(name) if name.starts_with('$') && name[1..].bytes().all(|b| b.is_ascii_digit()) => {
+                    Some((range, format!("?{}", &name[1..])))
+                },
+                _ => None,
+            })
+            .collect(),
+        Dialect::Postgres => question_marks(sql),
+    };
+
+    if replacements.is_empty() {
+        return std::borrow::Cow::Borrowed(sql);
+    }
+
+    let mut translated = String::with_capacity(sql.len());
+    let mut copied = 0;
+    for (range, replacement) in replacements {
+        translated.push_str(&sql[copied..range.start]);
+        translated.push_str(&replacement);
+        copied = range.end;
+    }
+    translated.push_str(&sql[copied..]);
+    std::borrow::Cow::Owned(translated)
+}
+
+// Finds `?` and `?N` in Postgres code, numbered as SQLite would number them
+fn question_marks(sql: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let mut found = vec![];
+    let mut highest = 0;
+
+    for token in tokenize(sql, Dialect::Postgres).into_iter().filter(|token| token.kind == TokenKind::Code) {
+        for (pos, _) in token.text.match_indices('?') {
+            let rest = &token.text[pos + 1..];
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let number = match rest[..digits].parse() {
+                Ok(n) => n,
+                Err(_) => highest + 1,
+            };
+            highest = highest.max(number);
+            let start = token.start + pos;
+            found.push((start..start + 1 + digits, format!("${number}")));
+        }
+    }
+
+    found
+}
+
+/// Checks that as many arguments are bound as the SQL has parameters, so that
+/// a mismatch fails with a message saying so, rather than whatever the host
+/// makes of it.
+pub(crate) fn check_arguments(sql: &str, dialect: Dialect, bound: usize) -> Result<(), sqlx::Error> {
+    let found = placeholders(sql, dialect);
+
+    // Numbered as the database numbers them
+    let mut expected = 0;
+    let mut named = vec![];
+    for (_, placeholder) in &found {
+        match placeholder {
+            Placeholder::Next => expected += 1,
+            Placeholder::Numbered(n) => expected = expected.max(*n),
+            Placeholder::Named(name) if !named.contains(name) => {
+                named.push(*name);
+                expected += 1;
+            },
+            Placeholder::Named(_) => (),
+        }
+    }
+
+    if expected == bound {
+        return Ok(());
+    }
+
+    let mut message = format!("query expects {expected} parameter{}, {bound} bound", if expected == 1 { "" } else { "s" });
+    if !found.is_empty() {
+        let positions = found.iter()
+            .map(|(range, _)| format!("{} at {}", &sql[range.clone()], line_and_column(sql, range.start)))
+            .collect::<Vec<_>>();
+        message.push_str(&format!(" (placeholders: {})", positions.join(", ")));
+    }
+    Err(sqlx::Error::Protocol(message))
+}
+
+fn line_and_column(sql: &str, pos: usize) -> String {
+    let before = &sql[..pos];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    format!("{line}:{column}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitting_statements() {
+        use Dialect::*;
+
+        let cases: &[(Dialect, &str, &[&str])] = &[
+            (Sqlite, "SELECT 1; SELECT 2;", &["SELECT 1", "SELECT 2"]),
+            (Sqlite, "SELECT 1;\nSELECT 2", &["SELECT 1", "SELECT 2"]),
+            (Sqlite, "SELECT 1", &["SELECT 1"]),
+            (Sqlite, "", &[]),
+            (Sqlite, " ;; ;\n", &[]),
+            (Sqlite, "SELECT 1;;SELECT 2", &["SELECT 1", "SELECT 2"]),
+            (Sqlite, "-- just a comment;\n/* and; another */", &[]),
+            (Sqlite, "SELECT 1; -- done; really\n", &["SELECT 1"]),
+            (Sqlite, "SELECT 1 /* ; */ + 1; SELECT 2", &["SELECT 1 /* ; */ + 1", "SELECT 2"]),
+            (Sqlite, "SELECT 1 -- one;\n+ 1; SELECT 2", &["SELECT 1 -- one;\n+ 1", "SELECT 2"]),
+            (Sqlite, "SELECT 'a;b', \"c;d\", [e;f], `g;h`; SELECT ''';'", &["SELECT 'a;b', \"c;d\", [e;f], `g;h`", "SELECT ''';'"]),
+            (Sqlite, "SELECT ''; ''", &["SELECT ''", "''"]),
+            (
+                Sqlite,
+                "CREATE TRIGGER t AFTER INSERT ON a BEGIN INSERT INTO b VALUES (1); DELETE FROM c; END; SELECT 1",
+                &["CREATE TRIGGER t AFTER INSERT ON a BEGIN INSERT INTO b VALUES (1); DELETE FROM c; END", "SELECT 1"],
+            ),
+            (
+                Sqlite,
+                "create temp trigger t after update on a begin update b set x = case when new.y then 1 else 2 end; end; select 1",
+                &["create temp trigger t after update on a begin update b set x = case when new.y then 1 else 2 end; end", "select 1"],
+            ),
+            (
+                Sqlite,
+                "CREATE TABLE t (x CASE); SELECT CASE WHEN 1 THEN 2 END; SELECT 3",
+                &["CREATE TABLE t (x CASE)", "SELECT CASE WHEN 1 THEN 2 END", "SELECT 3"],
+            ),
+            (Sqlite, "SELECT $a; SELECT 1", &["SELECT $a", "SELECT 1"]),
+            (Postgres, "SELECT $$a;b$$; SELECT $tag$ $$; $tag$", &["SELECT $$a;b$$", "SELECT $tag$ $$; $tag$"]),
+            (Postgres, "SELECT $1; SELECT a$b$; SELECT 2", &["SELECT $1", "SELECT a$b$", "SELECT 2"]),
+            (Postgres, "SELECT E'\\';'; SELECT 'a\\'; SELECT 2", &["SELECT E'\\';'", "SELECT 'a\\'", "SELECT 2"]),
+            (Postgres, "SELECT /* a /* b; */ c; */ 1; SELECT 2", &["SELECT /* a /* b; */ c; */ 1", "SELECT 2"]),
+            (
+                Postgres,
+                "CREATE OR REPLACE FUNCTION f() RETURNS int AS $body$ BEGIN RETURN 1; END; $body$ LANGUAGE plpgsql; SELECT f()",
+                &["CREATE OR REPLACE FUNCTION f() RETURNS int AS $body$ BEGIN RETURN 1; END; $body$ LANGUAGE plpgsql", "SELECT f()"],
+            ),
+            (
+                Postgres,
+                "CREATE PROCEDURE p() BEGIN ATOMIC INSERT INTO a VALUES (1); INSERT INTO a VALUES (2); END; CALL p()",
+                &["CREATE PROCEDURE p() BEGIN ATOMIC INSERT INTO a VALUES (1); INSERT INTO a VALUES (2); END", "CALL p()"],
+            ),
+            (Postgres, "SELECT 'unterminated; SELECT 2", &["SELECT 'unterminated; SELECT 2"]),
+        ];
+
+        for (dialect, sql, expected) in cases {
+            assert_eq!(*expected, split_statements(sql, *dialect), "{dialect:?}: {sql}");
+        }
+    }
+
+    #[test]
+    fn splitting_block_depth() {
+        let mut splitter = Splitter::default();
+        for word in "CREATE TRIGGER t BEGIN SELECT CASE WHEN 1 THEN CASE 2 END END".split(' ') {
+            splitter.word(word);
+        }
+        assert_eq!((true, 1), (splitter.has_body, splitter.block_depth));
+        splitter.word("END");
+        splitter.word("END");
+        assert_eq!(0, splitter.block_depth);
+
+        let mut splitter = Splitter::default();
+        for word in ["SELECT", "BEGIN", "CASE"] {
+            splitter.word(word);
+        }
+        assert_eq!((false, 0), (splitter.has_body, splitter.block_depth));
+    }
+
+    #[test]
+    fn single_statements_pass_through_unchanged() {
+        assert_eq!(["SELECT 1; -- done\n"], statements("SELECT 1; -- done\n", Dialect::Sqlite)[..]);
+        assert_eq!(["SELECT 1", "SELECT 2"], statements("SELECT 1; SELECT 2;", Dialect::Sqlite)[..]);
+    }
+
+    #[test]
+    fn translating_placeholders_for_postgres() {
+        let cases = [
+            ("SELECT ?, ?", "SELECT $1, $2"),
+            ("SELECT ?2, ?1, ?2", "SELECT $2, $1, $2"),
+            // A bare `?` takes the number after the highest so far, as in SQLite
+            ("SELECT ?, ?5, ?, ?2, ?", "SELECT $1, $5, $6, $2, $7"),
+            ("SELECT '?', \"a?\", ? -- ?\n/* ? */", "SELECT '?', \"a?\", $1 -- ?\n/* ? */"),
+            ("SELECT $$?$$, $t$ ? $t$, ?", "SELECT $$?$$, $t$ ? $t$, $1"),
+            ("SELECT E'\\'?', ?", "SELECT E'\\'?', $1"),
+            ("SELECT $1, $2", "SELECT $1, $2"),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(expected, translate_placeholders(sql, Dialect::Postgres), "{sql}");
+        }
+        assert!(matches!(translate_placeholders("SELECT '?'", Dialect::Postgres), std::borrow::Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn translating_placeholders_for_sqlite() {
+        let cases = [
+            ("SELECT $1, $2", "SELECT ?1, ?2"),
+            ("SELECT $2, $1, $2", "SELECT ?2, ?1, ?2"),
+            ("SELECT '$1', \"$1\", [$1], `$1`, $1 -- $2\n/* $3 */", "SELECT '$1', \"$1\", [$1], `$1`, ?1 -- $2\n/* $3 */"),
+            ("SELECT a$1, $1", "SELECT a$1, ?1"),
+            ("SELECT $name, :a, ?, ?3", "SELECT $name, :a, ?, ?3"),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(expected, translate_placeholders(sql, Dialect::Sqlite), "{sql}");
+        }
+        assert!(matches!(translate_placeholders("SELECT ?", Dialect::Sqlite), std::borrow::Cow::Borrowed(_)));
+    }
+
+    fn argument_error(sql: &str, dialect: Dialect, bound: usize) -> String {
+        match check_arguments(sql, dialect, bound) {
+            Err(sqlx::Error::Protocol(message)) => message,
+            other => panic!("expected a protocol error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn checking_argument_counts() {
+        use Dialect::*;
+
+        let cases = [
+            (Sqlite, "SELECT 1", 0),
+            (Sqlite, "SELECT ?, ?", 2),
+            (Sqlite, "SELECT ?3", 3),
+            (Sqlite, "SELECT ?2, ?, ?1", 3),
+            (Sqlite, "SELECT :a, @b, $c, :a", 3),
+            (Sqlite, "SELECT :a, ?1", 1),
+            (Sqlite, "SELECT ?2, :a", 3),
+            (Sqlite, "SELECT '?', \"?\", [?] -- ?", 0),
+            (Postgres, "SELECT $1, $2, $1", 2),
+            // Postgres numbers every parameter up to the highest, used or not
+            (Postgres, "SELECT $1, $3", 3),
+            (Postgres, "SELECT '$1', $$ $2 $$, a$3 /* $4 */", 0),
+        ];
+        for (dialect, sql, expected) in cases {
+            assert!(check_arguments(sql, dialect, expected).is_ok(), "{sql}");
+            assert!(check_arguments(sql, dialect, expected + 1).is_err(), "{sql}");
+            if expected > 0 {
+                assert!(check_arguments(sql, dialect, expected - 1).is_err(), "{sql}");
+            }
+        }
+    }
+
+    #[test]
+    fn argument_count_errors_say_where_the_placeholders_are() {
+        assert_eq!(
+            "query expects 3 parameters, 2 bound (placeholders: $1 at 1:8, $3 at 2:8)",
+            argument_error("SELECT $1,\n  'é', $3", Dialect::Postgres, 2),
+        );
+        assert_eq!(
+            "query expects 1 parameter, 2 bound (placeholders: :id at 1:27, :id at 1:38)",
+            argument_error("SELECT * FROM t WHERE a = :id OR b = :id", Dialect::Sqlite, 2),
+        );
+        assert_eq!("query expects 0 parameters, 1 bound", argument_error("SELECT 1", Dialect::Sqlite, 1));
+    }
+
+    #[test]
+    fn pageable_queries() {
+        use Dialect::*;
+
+        let cases = [
+            (Sqlite, "SELECT * FROM t ORDER BY id;", Some("SELECT * FROM t ORDER BY id")),
+            (Sqlite, "values (1), (2)", Some("values (1), (2)")),
+            (Sqlite, "WITH x AS (SELECT 1) SELECT * FROM x", Some("WITH x AS (SELECT 1) SELECT * FROM x")),
+            (Sqlite, "SELECT 'limit', \"offset\" -- for update", Some("SELECT 'limit', \"offset\" -- for update")),
+            (Sqlite, "SELECT * FROM t WHERE \"for\" = 'update'", Some("SELECT * FROM t WHERE \"for\" = 'update'")),
+            (Sqlite, "WITH x AS (DELETE FROM t RETURNING *) SELECT * FROM x", None),
+            (Sqlite, "INSERT INTO t VALUES (1) RETURNING *", None),
+            (Sqlite, "SELECT 1; SELECT 2", None),
+            (Sqlite, "", None),
+            (Sqlite, "SELECT * FROM t ORDER BY id LIMIT 10", None),
+            (Sqlite, "SELECT * FROM t ORDER BY id limit 10 offset 20", None),
+            (Sqlite, "SELECT * FROM t ORDER BY id LIMIT 20, 10", None),
+            (Postgres, "SELECT * FROM t ORDER BY id OFFSET 5", None),
+            (Postgres, "SELECT * FROM t ORDER BY id FETCH FIRST 10 ROWS ONLY", None),
+            (Postgres, "SELECT * FROM t FOR UPDATE", None),
+            (Postgres, "SELECT * FROM t for share skip locked", None),
+            (Postgres, "SELECT * FROM t FOR NO KEY UPDATE", None),
+            (Postgres, "SELECT * FROM t FOR KEY SHARE", None),
+            (Postgres, "SELECT * FROM t WHERE id IN (SELECT id FROM u FOR UPDATE)", None),
+        ];
+        for (dialect, sql, expected) in cases {
+            assert_eq!(expected, pageable_query(sql, dialect), "{dialect:?}: {sql}");
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use futures_core::future::BoxFuture;
+use futures_core::stream::BoxStream;
+use futures_util::TryStreamExt;
+use sqlx::{Column, ConnectOptions, Executor};
+use sqlx_core::any::driver::AnyDriver;
+use sqlx_core::any::{
+    Any, AnyArguments, AnyColumn, AnyConnectOptions, AnyConnectionBackend, AnyQueryResult, AnyRow,
+    AnyStatement, AnyTypeInfo, AnyTypeInfoKind, AnyValueKind,
+};
+use sqlx::Either;
+
+use crate::any::{column_names, unsupported, ColumnNames};
+use crate::sql::{counts_rows, statements, Dialect};
+
+use super::{Connection, ConnectionOptions, SpinSqliteArgs, SpinSqliteColumn, SpinSqliteRow, SpinSqliteTypeInfo};
+
+/// The `sqlx::any` driver for `spin-sqlite:` URLs.  See [`crate::install_any_drivers`].
+pub const ANY_DRIVER: AnyDriver = AnyDriver::with_migrate::<Connection>();
+
+impl AnyConnectionBackend for Connection {
+    fn name(&self) -> &str {
+        <Connection as sqlx::Database>::NAME
+    }
+
+    fn close(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        sqlx::Connection::close(*self)
+    }
+
+    fn close_hard(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        sqlx::Connection::close_hard(*self)
+    }
+
+    fn ping(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        sqlx::Connection::ping(self)
+    }
+
+    fn begin(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        <Connection as sqlx::TransactionManager>::begin(self)
+    }
+
+    fn commit(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        <Connection as sqlx::TransactionManager>::commit(self)
+    }
+
+    fn rollback(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        <Connection as sqlx::TransactionManager>::rollback(self)
+    }
+
+    fn start_rollback(&mut self) {
+        <Connection as sqlx::TransactionManager>::start_rollback(self)
+    }
+
+    fn shrink_buffers(&mut self) {
+    }
+
+    fn flush(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        sqlx::Connection::flush(self)
+    }
+
+    fn should_flush(&self) -> bool {
+        sqlx::Connection::should_flush(self)
+    }
+
+    fn as_migrate(&mut self) -> Result<&mut (dyn sqlx::migrate::Migrate + Send + 'static), sqlx::Error> {
+        Ok(self)
+    }
+
+    fn fetch_many<'q>(
+        &'q mut self,
+        query: &'q str,
+        _persistent: bool,
+        arguments: Option<AnyArguments<'q>>,
+    ) -> BoxStream<'q, Result<Either<AnyQueryResult, AnyRow>, sqlx::Error>> {
+        let args = match arguments.map(map_arguments).transpose() {
+            Ok(args) => args.unwrap_or_default(),
+            Err(e) => return Box::pin(futures::stream::once(async move { Err(e) })),
+        };
+
+        // Each statement's result says how many rows it changed, if it's a
+        // statement which changes rows, so work out which of them are
+        let conn = &*self;
+        let mut counts = statements(query, Dialect::Sqlite).into_iter().map(|sql| counts_rows(sql, Dialect::Sqlite));
+        let mut names = None;
+        let steps = conn.fetch_many(sqlx::query_with(query, args))
+            .and_then(move |step| {
+                let step = match step {
+                    Either::Left(_) => {
+                        names = None;  // the next statement's rows may have other columns
+                        Either::Left(counts.next().unwrap_or(false))
+                    },
+                    Either::Right(row) => Either::Right(map_row(&row, &mut names)),
+                };
+                async move {
+                    match step {
+                        Either::Left(counted) => query_result(conn, counted).await.map(Either::Left),
+                        Either::Right(row) => row.map(Either::Right),
+                    }
+                }
+            });
+
+        Box::pin(steps)
+    }
+
+    fn fetch_optional<'q>(
+        &'q mut self,
+        query: &'q str,
+        _persistent: bool,
+        arguments: Option<AnyArguments<'q>>,
+    ) -> BoxFuture<'q, Result<Option<AnyRow>, sqlx::Error>> {
+        Box::pin(async move {
+            let args = arguments.map(map_arguments).transpose()?.unwrap_or_default();
+            let row = (&*self).fetch_optional(sqlx::query_with(query, args)).await?;
+            row.map(|row| map_row(&row, &mut None)).transpose()
+        })
+    }
+
+    fn prepare_with<'c, 'q: 'c>(
+        &'c mut self,
+        _sql: &'q str,
+        _parameters: &[AnyTypeInfo],
+    ) -> BoxFuture<'c, Result<AnyStatement<'q>, sqlx::Error>> {
+        Box::pin(async move { Err(unsupported("prepared statements are", "Spin SQLite")) })
+    }
+
+    fn describe<'q>(&'q mut self, _sql: &'q str) -> BoxFuture<'q, Result<sqlx::Describe<Any>, sqlx::Error>> {
+        Box::pin(async move { Err(unsupported("describe is", "Spin SQLite")) })
+    }
+}
+
+fn map_row(row: &SpinSqliteRow, names: &mut Option<ColumnNames>) -> Result<AnyRow, sqlx::Error> {
+    let names = names.get_or_insert_with(|| column_names(row));
+    AnyRow::map_from(row, names.clone())
+}
+
+// Run as soon as the statement has, since `changes()` is that of the latest
+// statement which changed rows
+async fn query_result(conn: &Connection, counts: bool) -> Result<AnyQueryResult, sqlx::Error> {
+    if !counts {
+        return Ok(AnyQueryResult { rows_affected: 0, last_insert_id: None });
+    }
+    let (changes, last_insert_id): (i64, i64) = sqlx::query_as("SELECT changes(), last_insert_rowid()")
+        .fetch_one(conn)
+        .await?;
+    Ok(AnyQueryResult { rows_affected: changes as u64, last_insert_id: Some(last_insert_id) })
+}
+
+fn map_arguments(args: AnyArguments<'_>) -> Result<SpinSqliteArgs, sqlx::Error> {
+    let inner = args.values.0.into_iter()
+        .map(|value| match value {
+            AnyValueKind::Null(_) => Ok(spin_sdk::sqlite::Value::Null),
+            AnyValueKind::Bool(b) => Ok(spin_sdk::sqlite::Value::Integer(b.into())),
+            AnyValueKind::SmallInt(i) => Ok(spin_sdk::sqlite::Value::Integer(i.into())),
+            AnyValueKind::Integer(i) => Ok(spin_sdk::sqlite::Value::Integer(i.into())),
+            AnyValueKind::BigInt(i) => Ok(spin_sdk::sqlite::Value::Integer(i)),
+            AnyValueKind::Real(r) => Ok(spin_sdk::sqlite::Value::Real(r.into())),
+            AnyValueKind::Double(d) => Ok(spin_sdk::sqlite::Value::Real(d)),
+            AnyValueKind::Text(t) => Ok(spin_sdk::sqlite::Value::Text(t.into_owned())),
+            AnyValueKind::Blob(b) => Ok(spin_sdk::sqlite::Value::Blob(b.into_owned())),
+            other => Err(sqlx::Error::Encode(format!("Spin SQLite can't bind {other:?}").into())),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(SpinSqliteArgs { inner, named: vec![] })
+}
+
+impl<'a> TryFrom<&'a SpinSqliteTypeInfo> for AnyTypeInfo {
+    type Error = sqlx::Error;
+
+    fn try_from(type_info: &'a SpinSqliteTypeInfo) -> Result<Self, Self::Error> {
+        let kind = match type_info {
+            SpinSqliteTypeInfo::Int => AnyTypeInfoKind::BigInt,
+            SpinSqliteTypeInfo::Real => AnyTypeInfoKind::Double,
+            SpinSqliteTypeInfo::Text => AnyTypeInfoKind::Text,
+            SpinSqliteTypeInfo::Blob => AnyTypeInfoKind::Blob,
+            SpinSqliteTypeInfo::Null => AnyTypeInfoKind::Null,
+        };
+        Ok(AnyTypeInfo { kind })
+    }
+}
+
+impl<'a> TryFrom<&'a SpinSqliteColumn> for AnyColumn {
+    type Error = sqlx::Error;
+
+    fn try_from(column: &'a SpinSqliteColumn) -> Result<Self, Self::Error> {
+        Ok(AnyColumn {
+            ordinal: column.ordinal(),
+            name: column.name().to_owned().into(),
+            type_info: AnyTypeInfo::try_from(column.type_info())?,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a AnyConnectOptions> for ConnectionOptions {
+    type Error = sqlx::Error;
+
+    fn try_from(options: &'a AnyConnectOptions) -> Result<Self, Self::Error> {
+        ConnectionOptions::from_url(&options.database_url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Arguments, Row};
+    use spin_sdk::sqlite::Value;
+
+    use super::*;
+
+    #[test]
+    fn arguments_are_mapped_to_sqlite_values() {
+        let mut args = AnyArguments::default();
+        args.add(true).unwrap();
+        args.add(-2i16).unwrap();
+        args.add(3i32).unwrap();
+        args.add(i64::MAX).unwrap();
+        args.add(0.5f32).unwrap();
+        args.add(0.25f64).unwrap();
+        args.add("text").unwrap();
+        args.add(vec![0u8, 255]).unwrap();
+        args.add(Option::<i32>::None).unwrap();
+
+        let values = map_arguments(args).unwrap().inner.iter().map(|value| format!("{value:?}").replace("Value::", "")).collect::<Vec<_>>();
+        assert_eq!(
+            ["Integer(1)", "Integer(-2)", "Integer(3)", "Integer(9223372036854775807)", "Real(0.5)", "Real(0.25)", "Text(\"text\")", "Blob([0, 255])", "Null"],
+            values[..],
+        );
+    }
+
+    #[test]
+    fn type_infos_are_mapped_to_any_kinds() {
+        let cases = [
+            (SpinSqliteTypeInfo::Int, AnyTypeInfoKind::BigInt),
+            (SpinSqliteTypeInfo::Real, AnyTypeInfoKind::Double),
+            (SpinSqliteTypeInfo::Text, AnyTypeInfoKind::Text),
+            (SpinSqliteTypeInfo::Blob, AnyTypeInfoKind::Blob),
+            (SpinSqliteTypeInfo::Null, AnyTypeInfoKind::Null),
+        ];
+        for (type_info, kind) in cases {
+            assert_eq!(kind, AnyTypeInfo::try_from(&type_info).unwrap().kind, "{type_info:?}");
+        }
+    }
+
+    #[test]
+    fn rows_are_mapped_to_any_rows() {
+        let row = SpinSqliteRow::for_test(vec![
+            ("id", Value::Integer(7)),
+            ("score", Value::Real(1.5)),
+            ("name", Value::Text("Fido".into())),
+            ("data", Value::Blob(vec![1, 2])),
+            ("missing", Value::Null),
+        ]);
+        let mut names = None;
+        let any_row = map_row(&row, &mut names).unwrap();
+
+        assert_eq!(7, any_row.try_get::<i64, _>("id").unwrap());
+        assert_eq!(1.5, any_row.try_get::<f64, _>("score").unwrap());
+        assert_eq!("Fido", any_row.try_get::<String, _>(2).unwrap());
+        assert_eq!(vec![1, 2], any_row.try_get::<Vec<u8>, _>("data").unwrap());
+        assert_eq!(None, any_row.try_get::<Option<String>, _>("missing").unwrap());
+        assert_eq!(["id", "score", "name", "data", "missing"], any_row.columns().iter().map(|c| c.name()).collect::<Vec<_>>()[..]);
+
+        // Later rows of the same result share the column names
+        let names = names.unwrap();
+        let next = map_row(&row, &mut Some(names.clone())).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&names, &next.column_names));
+    }
+}
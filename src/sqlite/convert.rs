@@ -200,6 +200,7 @@ impl<'r> sqlx::Decode<'r, Connection> for f32 {
     fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         match value.inner {
             spin_sdk::sqlite::Value::Real(n) => Ok(n as f32),  // `as` is the best conversion we have
+            spin_sdk::sqlite::Value::Integer(n) => Ok(n as f32),
             _ => Err(Box::new(BadTypeError)),
         }
     }
@@ -208,6 +209,10 @@ impl sqlx::Type<Connection> for f32 {
     fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
         SpinSqliteTypeInfo::Real
     }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        // SQLite may hand back an INTEGER for a whole-number value in a NUMERIC column
+        matches!(ty, SpinSqliteTypeInfo::Real | SpinSqliteTypeInfo::Int)
+    }
 }
 
 impl<'q> sqlx::Encode<'q, Connection> for f64 {
@@ -220,6 +225,7 @@ impl<'r> sqlx::Decode<'r, Connection> for f64 {
     fn decode(value: <Connection as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         match value.inner {
             spin_sdk::sqlite::Value::Real(n) => Ok(n),
+            spin_sdk::sqlite::Value::Integer(n) => Ok(n as f64),
             _ => Err(Box::new(BadTypeError)),
         }
     }
@@ -228,6 +234,10 @@ impl sqlx::Type<Connection> for f64 {
     fn type_info() -> <Connection as sqlx::Database>::TypeInfo {
         SpinSqliteTypeInfo::Real
     }
+    fn compatible(ty: &<Connection as sqlx::Database>::TypeInfo) -> bool {
+        // SQLite may hand back an INTEGER for a whole-number value in a NUMERIC column
+        matches!(ty, SpinSqliteTypeInfo::Real | SpinSqliteTypeInfo::Int)
+    }
 }
 
 impl<'q> sqlx::Encode<'q, Connection> for &[u8] {
@@ -277,3 +287,61 @@ impl<'q, T: sqlx::Encode<'q, Connection>> sqlx::Encode<'q, Connection> for Optio
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{FromRow, Row, Type};
+    use spin_sdk::sqlite::Value;
+
+    use super::super::{Connection, SpinSqliteRow, SpinSqliteTypeInfo};
+
+    #[derive(Debug, PartialEq, sqlx::FromRow)]
+    struct Pet {
+        name: String,
+        age: Option<i64>,
+    }
+
+    #[test]
+    fn from_row_decodes_null_as_none() {
+        let pet = Pet::from_row(&SpinSqliteRow::for_test(vec![("name", Value::Text("Slats".into())), ("age", Value::Null)])).unwrap();
+        assert_eq!(Pet { name: "Slats".into(), age: None }, pet);
+    }
+
+    #[test]
+    fn from_row_decodes_value_as_some() {
+        let pet = Pet::from_row(&SpinSqliteRow::for_test(vec![("name", Value::Text("Hobbes".into())), ("age", Value::Integer(1))])).unwrap();
+        assert_eq!(Pet { name: "Hobbes".into(), age: Some(1) }, pet);
+    }
+
+    #[test]
+    fn scalar_option_decodes_null_and_value() {
+        // `query_scalar` decodes its rows as one-element tuples
+        let (none,) = <(Option<i64>,)>::from_row(&SpinSqliteRow::for_test(vec![("n", Value::Null)])).unwrap();
+        let (some,) = <(Option<i64>,)>::from_row(&SpinSqliteRow::for_test(vec![("n", Value::Integer(42))])).unwrap();
+        assert_eq!(None, none);
+        assert_eq!(Some(42), some);
+    }
+
+    #[test]
+    fn option_still_checks_the_type_of_non_null_values() {
+        let row = SpinSqliteRow::for_test(vec![("n", Value::Text("42".into()))]);
+        assert!(row.try_get::<Option<i64>, _>("n").is_err());
+    }
+
+    #[test]
+    fn floats_decode_from_integers() {
+        let row = SpinSqliteRow::for_test(vec![("n", Value::Integer(2)), ("x", Value::Real(2.5))]);
+        assert_eq!(2.0, row.try_get::<f64, _>("n").unwrap());
+        assert_eq!(2.0, row.try_get::<f32, _>("n").unwrap());
+        assert_eq!(Some(2.0), row.try_get::<Option<f64>, _>("n").unwrap());
+        assert_eq!(2.5, row.try_get::<f64, _>("x").unwrap());
+    }
+
+    #[test]
+    fn float_compatibility_includes_integers_but_not_the_reverse() {
+        assert!(<f64 as Type<Connection>>::compatible(&SpinSqliteTypeInfo::Int));
+        assert!(<f32 as Type<Connection>>::compatible(&SpinSqliteTypeInfo::Int));
+        assert!(!<f64 as Type<Connection>>::compatible(&SpinSqliteTypeInfo::Text));
+        assert!(!<i64 as Type<Connection>>::compatible(&SpinSqliteTypeInfo::Real));
+    }
+}
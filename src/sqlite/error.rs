@@ -68,16 +68,3 @@ impl std::fmt::Display for DatabaseFull {
         f.write_str(self.message())
     }
 }
-
-pub(crate) trait UrlParseResult<T> {
-    fn as_sqlx_result(self) -> Result<T, sqlx::Error>;
-}
-
-impl<T> UrlParseResult<url::Host<T>> for Option<url::Host<T>> {
-    fn as_sqlx_result(self) -> Result<url::Host<T>, sqlx::Error> {
-        match self {
-            None => Err(sqlx::Error::Configuration(anyhow!("Invalid URL").into())),
-            Some(val) => Ok(val)
-        }
-    }
-}
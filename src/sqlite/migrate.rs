@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use futures_core::future::BoxFuture;
+use sqlx::migrate::{AppliedMigration, Migrate, MigrateDatabase, MigrateError, Migration, Migrator};
+use sqlx::{query, query_as, Executor};
+
+use crate::sql::{split_statements, Dialect};
+
+use super::error::AsSqlxResult;
+use super::Connection;
+
+// Spin SQLite runs only one statement per call, so scripts must be split up
+async fn execute_script(conn: &Connection, sql: &str) -> Result<(), sqlx::Error> {
+    for statement in split_statements(sql, Dialect::Sqlite) {
+        conn.execute(statement).await?;
+    }
+    Ok(())
+}
+
+async fn finish_transaction(conn: &Connection, result: Result<(), MigrateError>) -> Result<(), MigrateError> {
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT").await?;
+            Ok(())
+        },
+        Err(e) => {
+            // The original error is more use than any failure to roll back
+            let _ = conn.execute("ROLLBACK").await;
+            Err(e)
+        },
+    }
+}
+
+/// Lets `sqlx::migrate::Migrator` apply migrations over a connection:
+///
+/// ```no_run
+/// # async fn migrate(conn: &mut spin_sqlx::sqlite::Connection) -> Result<(), sqlx::migrate::MigrateError> {
+/// // or `static MIGRATOR: Migrator = sqlx::migrate!();`
+/// let migrator = sqlx::migrate::Migrator::new(std::path::Path::new("./migrations")).await?;
+/// migrator.run(conn).await?;
+/// # Ok(())
+/// # }
+/// ```
+impl Migrate for Connection {
+    fn ensure_migrations_table(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(
+                r#"
+CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    success BOOLEAN NOT NULL,
+    checksum BLOB NOT NULL,
+    execution_time BIGINT NOT NULL
+)
+                "#,
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn dirty_version(&mut self) -> BoxFuture<'_, Result<Option<i64>, MigrateError>> {
+        Box::pin(async move {
+            let row: Option<(i64,)> = query_as(
+                "SELECT version FROM _sqlx_migrations WHERE success = false ORDER BY version LIMIT 1",
+            )
+            .fetch_optional(&*self)
+            .await?;
+
+            Ok(row.map(|r| r.0))
+        })
+    }
+
+    fn list_applied_migrations(&mut self) -> BoxFuture<'_, Result<Vec<AppliedMigration>, MigrateError>> {
+        Box::pin(async move {
+            let rows: Vec<(i64, Vec<u8>)> =
+                query_as("SELECT version, checksum FROM _sqlx_migrations ORDER BY version")
+                    .fetch_all(&*self)
+                    .await?;
+
+            let migrations = rows
+                .into_iter()
+                .map(|(version, checksum)| AppliedMigration { version, checksum: checksum.into() })
+                .collect();
+
+            Ok(migrations)
+        })
+    }
+
+    // Each migration runs in its own transaction, which SQLite serialises, so
+    // there's nothing more to lock.
+    fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn unlock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn apply<'e: 'm, 'm>(&'e mut self, migration: &'m Migration) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(async move {
+            let conn = &*self;
+            let start = Instant::now();
+
+            // As in sqlx, the script and the bookkeeping share a transaction so that a
+            // migration can never be applied twice.  The execution time is recorded
+            // afterwards, so it's -1 until then.
+            let run = async {
+                execute_script(conn, &migration.sql).await
+                    .map_err(|e| MigrateError::ExecuteMigration(e, migration.version))?;
+
+                query(
+                    r#"
+    INSERT INTO _sqlx_migrations ( version, description, success, checksum, execution_time )
+    VALUES ( ?1, ?2, TRUE, ?3, -1 )
+                    "#,
+                )
+                .bind(migration.version)
+                .bind(&*migration.description)
+                .bind(&*migration.checksum)
+                .execute(conn)
+                .await?;
+
+                Ok(())
+            };
+
+            if migration.no_tx {
+                run.await?;
+            } else {
+                conn.execute("BEGIN").await?;
+                finish_transaction(conn, run.await).await?;
+            }
+
+            let elapsed = start.elapsed();
+
+            query(
+                r#"
+    UPDATE _sqlx_migrations
+    SET execution_time = ?1
+    WHERE version = ?2
+                "#,
+            )
+            .bind(elapsed.as_nanos() as i64)
+            .bind(migration.version)
+            .execute(conn)
+            .await?;
+
+            Ok(elapsed)
+        })
+    }
+
+    fn revert<'e: 'm, 'm>(&'e mut self, migration: &'m Migration) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(async move {
+            let conn = &*self;
+            let start = Instant::now();
+
+            let run = async {
+                execute_script(conn, &migration.sql).await
+                    .map_err(|e| MigrateError::ExecuteMigration(e, migration.version))?;
+
+                query("DELETE FROM _sqlx_migrations WHERE version = ?1")
+                    .bind(migration.version)
+                    .execute(conn)
+                    .await?;
+
+                Ok(())
+            };
+
+            if migration.no_tx {
+                run.await?;
+            } else {
+                conn.execute("BEGIN").await?;
+                finish_transaction(conn, run.await).await?;
+            }
+
+            Ok(start.elapsed())
+        })
+    }
+}
+
+// Spin creates and removes SQLite databases itself, according to the manifest
+// and runtime config, so only the existence check is meaningful here.  The URL
+// is anything `ConnectionOptions` can parse, such as `spin-sqlite://label`.
+impl MigrateDatabase for Connection {
+    fn create_database(_url: &str) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            Err(sqlx::Error::Configuration("Spin SQLite databases are created by Spin, not by the application".into()))
+        })
+    }
+
+    fn database_exists(url: &str) -> BoxFuture<'_, Result<bool, sqlx::Error>> {
+        Box::pin(async move {
+            let options: super::ConnectionOptions = url.parse()?;
+            match spin_sdk::sqlite::Connection::open(options.get_label()) {
+                Ok(_) => Ok(true),
+                Err(spin_sdk::sqlite::Error::NoSuchDatabase) => Ok(false),
+                Err(e) => Err(sqlx::Error::AnyDriverError(Box::new(e))),
+            }
+        })
+    }
+
+    fn drop_database(_url: &str) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            Err(sqlx::Error::Configuration("Spin SQLite databases are removed by Spin, not by the application".into()))
+        })
+    }
+}
+
+/// A migration applied by [`migrate`].
+#[derive(Clone, Debug)]
+pub struct MigrationRun {
+    pub version: i64,
+    pub description: String,
+    pub elapsed: Duration,
+}
+
+/// What [`migrate`] did.
+#[derive(Clone, Debug, Default)]
+pub struct MigrationReport {
+    /// The migrations applied by this call, in the order they were applied.
+    pub applied: Vec<MigrationRun>,
+    /// The versions of migrations which had already been applied.
+    pub already_applied: Vec<i64>,
+}
+
+/// Applies any pending migrations to the SQLite database with the given label.
+///
+/// Several component instances may cold-start at once against the same database,
+/// so this takes the database write lock (`BEGIN IMMEDIATE`) before looking at
+/// which migrations have been applied, and holds it until it has applied them all.
+/// Each migration is therefore applied exactly once: instances that had to wait
+/// for the lock find there is nothing left to do.
+///
+/// Everything happens in that one transaction, so if any migration fails, none of
+/// this call's migrations are kept.  For the same reason, migrations marked
+/// `-- no-transaction` can't be applied this way.
+pub async fn migrate(label: &str, migrator: &Migrator) -> Result<MigrationReport, MigrateError> {
+    let mut conn = Connection::new(spin_sdk::sqlite::Connection::open(label).as_sqlx_result()?);
+
+    // Wait for another instance's lock rather than failing immediately.  Not all
+    // Spin SQLite backends support this, so carry on if it's refused.
+    let _ = conn.execute("PRAGMA busy_timeout = 30000").await;
+
+    conn.execute("BEGIN IMMEDIATE").await?;
+
+    match apply_pending(&mut conn, migrator).await {
+        Ok(report) => {
+            conn.execute("COMMIT").await?;
+            Ok(report)
+        },
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK").await;
+            Err(e)
+        },
+    }
+}
+
+async fn apply_pending(conn: &mut Connection, migrator: &Migrator) -> Result<MigrationReport, MigrateError> {
+    conn.ensure_migrations_table().await?;
+
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+
+    let applied: HashMap<_, _> = conn.list_applied_migrations().await?
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    if !migrator.ignore_missing {
+        if let Some(version) = applied.keys().find(|v| !migrator.version_exists(**v)) {
+            return Err(MigrateError::VersionMissing(*version));
+        }
+    }
+
+    let mut report = MigrationReport::default();
+
+    for migration in migrator.iter().filter(|m| !m.migration_type.is_down_migration()) {
+        if let Some(applied_migration) = applied.get(&migration.version) {
+            if migration.checksum != applied_migration.checksum {
+                return Err(MigrateError::VersionMismatch(migration.version));
+            }
+            report.already_applied.push(migration.version);
+            continue;
+        }
+
+        if migration.no_tx {
+            let message = format!("migration {} is marked no-transaction, so can't be applied under the migration lock", migration.version);
+            return Err(MigrateError::ExecuteMigration(sqlx::Error::Configuration(message.into()), migration.version));
+        }
+
+        let start = Instant::now();
+        execute_script(conn, &migration.sql).await
+            .map_err(|e| MigrateError::ExecuteMigration(e, migration.version))?;
+        let elapsed = start.elapsed();
+
+        query(
+            r#"
+    INSERT INTO _sqlx_migrations ( version, description, success, checksum, execution_time )
+    VALUES ( ?1, ?2, TRUE, ?3, ?4 )
+            "#,
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .bind(elapsed.as_nanos() as i64)
+        .execute(&*conn)
+        .await?;
+
+        report.applied.push(MigrationRun {
+            version: migration.version,
+            description: migration.description.to_string(),
+            elapsed,
+        });
+    }
+
+    Ok(report)
+}
@@ -1,39 +1,138 @@
+use std::borrow::Cow;
+use std::future::ready;
+
 use futures_core::future::BoxFuture;
 use futures_core::stream::BoxStream;
-use log::LevelFilter;
+use futures_core::Stream;
+use futures_util::{StreamExt, TryStreamExt};
 
+mod any;
 mod convert;
 mod error;
+mod migrate;
+mod named;
+mod options;
+mod pool;
 mod query_result;
 mod type_info;
 
-use error::UrlParseResult;
-
+pub use any::ANY_DRIVER;
+pub use migrate::{migrate, MigrationReport, MigrationRun};
+pub use named::{query_named, NamedQuery};
+pub use options::{ConnectionOptions, JournalMode, Synchronous};
+pub use pool::{SpinSqlitePool, SpinSqlitePoolConnection};
 pub use query_result::{SpinSqliteColumn, SpinSqliteQueryResult, SpinSqliteRow, SpinSqliteValue};
 pub use type_info::SpinSqliteTypeInfo;
 
 use error::AsSqlxResult;
 
+use crate::sql::{
+    arguments_for_several_statements, check_arguments, page_sql, pageable_query, statements, translate_placeholders,
+    Dialect,
+};
+
 #[derive(Debug)]
-pub struct Connection(spin_sdk::sqlite::Connection);
+pub struct Connection {
+    inner: spin_sdk::sqlite::Connection,
+    fetch_page_size: Option<usize>,
+    portable_placeholders: bool,
+    transaction_depth: usize,
+}
 
 impl Connection {
     pub fn new(conn: spin_sdk::sqlite::Connection) -> Self {
-        Self(conn)
+        Self { inner: conn, fetch_page_size: None, portable_placeholders: false, transaction_depth: 0 }
     }
 
     pub fn open(label: &str) -> anyhow::Result<Self> {
-        Ok(Self(spin_sdk::sqlite::Connection::open(label)?))
+        Ok(Self::new(spin_sdk::sqlite::Connection::open(label)?))
     }
 
     pub fn open_default() -> anyhow::Result<Self> {
-        Ok(Self(spin_sdk::sqlite::Connection::open_default()?))
+        Ok(Self::new(spin_sdk::sqlite::Connection::open_default()?))
+    }
+
+    /// The Spin connection, for things this crate doesn't support.  Statements
+    /// run on it share this connection's transaction, if there is one.
+    pub fn as_inner(&self) -> &spin_sdk::sqlite::Connection {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> spin_sdk::sqlite::Connection {
+        self.inner
+    }
+
+    /// Streams the rows of queries (`fetch`, `fetch_many`) a page of `size` rows
+    /// at a time, rather than reading them all into memory at once.  Each page is
+    /// read only when the rows before it have been, by running the query again
+    /// wrapped in `LIMIT ... OFFSET ...`, so give such queries an `ORDER BY` to
+    /// keep pages from overlapping, and distinct column names.  Queries with a
+    /// `LIMIT` or `OFFSET` of their own are read whole.  A size of 0, the
+    /// default, turns paging off.
+    pub fn fetch_page_size(mut self, size: usize) -> Self {
+        self.fetch_page_size = (size > 0).then_some(size);
+        self
+    }
+
+    /// Accepts Postgres-style `$N` placeholders as well as SQLite's own, by
+    /// rewriting them to `?N`, so that the same SQL can run on both backends.
+    /// Placeholders in string literals, quoted identifiers and comments are
+    /// left alone.  See also [`crate::pg::Connection::portable_placeholders`].
+    pub fn portable_placeholders(mut self, portable: bool) -> Self {
+        self.portable_placeholders = portable;
+        self
+    }
+
+    // The SQL to run for a query
+    fn sql<'q>(&self, sql: &'q str) -> Cow<'q, str> {
+        if self.portable_placeholders {
+            translate_placeholders(sql, Dialect::Sqlite)
+        } else {
+            Cow::Borrowed(sql)
+        }
+    }
+
+    // Runs the query for each page only once the rows of the page before it
+    // have been read
+    fn fetch_pages<'a>(
+        &'a self,
+        sql: String,
+        args: SpinSqliteArgs,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<sqlx::Either<SpinSqliteQueryResult, SpinSqliteRow>, sqlx::Error>> + 'a {
+        futures::stream::unfold(Some(0), move |offset| {
+            let page = offset.map(|offset| {
+                match self.inner.execute(&page_sql(&sql, page_size, offset), args.as_slice()).as_sqlx_result() {
+                    Ok(rs) => {
+                        let read = rs.rows.len();
+                        let mut steps = statement_steps(rs);
+                        if read < page_size {
+                            (Ok(steps), None)
+                        } else {
+                            steps.pop();  // the page's result: the query isn't finished
+                            (Ok(steps), Some(offset + read))
+                        }
+                    },
+                    Err(e) => (Err(e), None),
+                }
+            });
+            ready(page)
+        })
+        .map_ok(|steps| futures::stream::iter(steps.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+impl From<spin_sdk::sqlite::Connection> for Connection {
+    fn from(conn: spin_sdk::sqlite::Connection) -> Self {
+        Self::new(conn)
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct ConnectionOptions {
-    label: String,
+impl From<Connection> for spin_sdk::sqlite::Connection {
+    fn from(conn: Connection) -> Self {
+        conn.inner
+    }
 }
 
 impl sqlx::Connection for Connection {
@@ -55,7 +154,7 @@ impl sqlx::Connection for Connection {
     fn begin(&mut self) -> BoxFuture<'_, Result<sqlx::Transaction<'_, Self::Database>, sqlx::Error>>
     where
         Self: Sized {
-        todo!()
+        sqlx::Transaction::begin(self)
     }
 
     fn shrink_buffers(&mut self) {
@@ -95,9 +194,33 @@ impl sqlx::Database for Connection {
     const URL_SCHEMES: &'static [&'static str] = &["spin-sqlite"];
 }
 
+/// The arguments bound to a SQLite query.
+///
+/// These use `?` placeholders, which SQLite numbers in order, so
+/// `sqlx::QueryBuilder` works:
+///
+/// ```
+/// use spin_sqlx::sqlite::Connection as Sqlite;
+///
+/// let pets = [("Fido", 3), ("Rex", 5)];
+/// let mut builder = sqlx::QueryBuilder::<Sqlite>::new("INSERT INTO pets (name, age) ");
+/// builder.push_values(pets, |mut row, (name, age)| {
+///     row.push_bind(name).push_bind(age);
+/// });
+/// assert_eq!("INSERT INTO pets (name, age) VALUES (?, ?), (?, ?)", builder.sql());
+///
+/// let mut builder = sqlx::QueryBuilder::<Sqlite>::new("SELECT * FROM pets WHERE age IN (");
+/// let mut ages = builder.separated(", ");
+/// for age in [1, 2, 3] {
+///     ages.push_bind(age);
+/// }
+/// builder.push(")");
+/// assert_eq!("SELECT * FROM pets WHERE age IN (?, ?, ?)", builder.sql());
+/// ```
 #[derive(Default)]
 pub struct SpinSqliteArgs {
     inner: Vec<spin_sdk::sqlite::Value>,
+    named: Vec<(String, spin_sdk::sqlite::Value)>,
 }
 
 impl SpinSqliteArgs {
@@ -123,6 +246,10 @@ impl<'q> sqlx::Arguments<'q> for SpinSqliteArgs {
     fn len(&self) -> usize {
         self.inner.len()
     }
+
+    fn format_placeholder<W: std::fmt::Write>(&self, writer: &mut W) -> std::fmt::Result {
+        writer.write_char('?')
+    }
 }
 
 impl<'q> sqlx::IntoArguments<'q, Connection> for SpinSqliteArgs {
@@ -207,65 +334,57 @@ impl<'q> sqlx::Statement<'q> for SpinSqliteStmt {
     }
 }
 
+sqlx_core::impl_acquire!(Connection, Connection);
+
 impl sqlx::TransactionManager for Connection {
     type Database = Connection;
 
+    // Nested transactions are savepoints, as in sqlx's own drivers
     fn begin(
-        _conn: &mut <Self::Database as sqlx::Database>::Connection,
+        conn: &mut <Self::Database as sqlx::Database>::Connection,
     ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
-        todo!("transactions are not implemented for Spin SQLite")
+        Box::pin(async move {
+            let sql = sqlx_core::transaction::begin_ansi_transaction_sql(conn.transaction_depth);
+            sqlx::Executor::execute(&*conn, &*sql).await?;
+            conn.transaction_depth += 1;
+            Ok(())
+        })
     }
 
     fn commit(
-        _conn: &mut <Self::Database as sqlx::Database>::Connection,
+        conn: &mut <Self::Database as sqlx::Database>::Connection,
     ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
-        todo!("transactions are not implemented for Spin SQLite")
+        Box::pin(async move {
+            if conn.transaction_depth > 0 {
+                let sql = sqlx_core::transaction::commit_ansi_transaction_sql(conn.transaction_depth);
+                sqlx::Executor::execute(&*conn, &*sql).await?;
+                conn.transaction_depth -= 1;
+            }
+            Ok(())
+        })
     }
 
     fn rollback(
-        _conn: &mut <Self::Database as sqlx::Database>::Connection,
+        conn: &mut <Self::Database as sqlx::Database>::Connection,
     ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
-        todo!("transactions are not implemented for Spin SQLite")
-    }
-
-    fn start_rollback(_conn: &mut <Self::Database as sqlx::Database>::Connection) {
-        todo!("transactions are not implemented for Spin SQLite")
-    }
-}
-
-impl std::str::FromStr for ConnectionOptions {
-    type Err = sqlx::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self { label: s.to_owned() })
-    }
-}
-
-impl sqlx::ConnectOptions for ConnectionOptions {
-    type Connection = Connection;
-
-    fn from_url(url: &url::Url) -> Result<Self, sqlx::Error> {
-        let label = url.host().as_sqlx_result()?.to_string();
-        Ok(Self { label })
-    }
-
-    fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, sqlx::Error>>
-    where
-        Self::Connection: Sized
-    {
         Box::pin(async move {
-            spin_sdk::sqlite::Connection::open(&self.label)
-                .map(|conn| Connection(conn))
-                .map_err(|e| sqlx::Error::AnyDriverError(Box::new(e)))
+            if conn.transaction_depth > 0 {
+                let sql = sqlx_core::transaction::rollback_ansi_transaction_sql(conn.transaction_depth);
+                sqlx::Executor::execute(&*conn, &*sql).await?;
+                conn.transaction_depth -= 1;
+            }
+            Ok(())
         })
     }
 
-    fn log_statements(self, _level: LevelFilter) -> Self {
-        self
-    }
-
-    fn log_slow_statements(self, _level: LevelFilter, _duration: std::time::Duration) -> Self {
-        self
+    // Called when a transaction is dropped without being committed.  Spin runs
+    // statements synchronously, so this can roll back there and then.
+    fn start_rollback(conn: &mut <Self::Database as sqlx::Database>::Connection) {
+        if conn.transaction_depth > 0 {
+            let sql = sqlx_core::transaction::rollback_ansi_transaction_sql(conn.transaction_depth);
+            let _ = conn.inner.execute(&sql, &[]);
+            conn.transaction_depth -= 1;
+        }
     }
 }
 
@@ -288,27 +407,41 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
     {
         tracing::debug!("FETCH-MANYing {}", query.sql());
         // The args-exec dance needs to go on the SqlxConnection object
-        let args = match query.take_arguments() {
-            Ok(a) => a.unwrap_or_default(),
-            Err(e) => {
-                return Box::pin(futures::stream::once(async move { Err(sqlx::Error::Encode(e)) }));
-            }
-        };
-        let rs = match self.0.execute(query.sql(), args.as_slice()).as_sqlx_result() {
-            Ok(rs) => rs,
+        let args = match take_arguments(&mut query) {
+            Ok(a) => a,
             Err(e) => {
                 return Box::pin(futures::stream::once(async move { Err(e) }));
             }
         };
-
-        // Okay this CANNOT return a QueryResult because fetch will filtermap any
-        // Either::Lefts away because reasons.  We have to get the rows.
-
-        let columns_core = rs.columns.iter().enumerate().map(|(i, c)| SpinSqliteColumn::new(i, c)).collect::<Vec<_>>();
-        let columns = std::sync::Arc::new(columns_core);
-        let rows = rs.rows.into_iter()
-            .map(move |r| Ok(sqlx::Either::Right(SpinSqliteRow { columns: columns.clone(), inner: r })));
-        Box::pin(futures::stream::iter(rows))
+        let sql = self.sql(query.sql());
+        if let Err(e) = check_arguments(&sql, Dialect::Sqlite, args.inner.len()) {
+            return Box::pin(futures::stream::once(async move { Err(e) }));
+        }
+        if let Some(page_size) = self.fetch_page_size {
+            if let Some(sql) = pageable_query(&sql, Dialect::Sqlite) {
+                return Box::pin(self.fetch_pages(sql.to_owned(), args, page_size));
+            }
+        }
+
+        let statements = statements(&sql, Dialect::Sqlite).into_iter().map(str::to_owned).collect::<Vec<_>>();
+        if statements.len() > 1 && !args.inner.is_empty() {
+            return Box::pin(futures::stream::once(async move { Err(arguments_for_several_statements()) }));
+        }
+
+        // Spin runs one statement per call.  The statements run as the stream is
+        // read, and the first to fail ends it, as in a script.
+        let steps = futures::stream::iter(statements)
+            .map(move |sql| self.inner.execute(&sql, args.as_slice()).as_sqlx_result())
+            .scan(false, |failed, rs| {
+                if *failed {
+                    return ready(None);
+                }
+                *failed = rs.is_err();
+                ready(Some(rs))
+            })
+            .map_ok(|rs| futures::stream::iter(statement_steps(rs).into_iter().map(Ok)))
+            .try_flatten();
+        Box::pin(steps)
     }
 
     fn execute<'e, 'q: 'e, E: 'q>(
@@ -320,13 +453,20 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
             E: sqlx::Execute<'q, Self::Database>,
     {
         tracing::debug!("EXECing {}", query.sql());
-        let args = match query.take_arguments() {
-            Ok(a) => a.unwrap_or_default(),
+        if statements(query.sql(), Dialect::Sqlite).len() > 1 {
+            return Box::pin(self.fetch_many(query).try_filter_map(|step| ready(Ok(step.left()))).try_collect());
+        }
+        let args = match take_arguments(&mut query) {
+            Ok(a) => a,
             Err(e) => {
-                return Box::pin(async move { Err(sqlx::Error::Encode(e)) });
+                return Box::pin(async move { Err(e) });
             }
         };
-        let rs = match self.0.execute(query.sql(), args.as_slice()).as_sqlx_result() {
+        let sql = self.sql(query.sql());
+        if let Err(e) = check_arguments(&sql, Dialect::Sqlite, args.inner.len()) {
+            return Box::pin(async move { Err(e) });
+        }
+        let rs = match self.inner.execute(&sql, args.as_slice()).as_sqlx_result() {
             Ok(rs) => rs,
             Err(e) => {
                 return Box::pin(async move { Err(e) });
@@ -347,13 +487,21 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
         E: sqlx::Execute<'q, Self::Database>
     {
         tracing::debug!("FETCH-OPTIONALing {}", query.sql());
-        let args = match query.take_arguments() {
-            Ok(a) => a.unwrap_or_default(),
+        if statements(query.sql(), Dialect::Sqlite).len() > 1 {
+            let mut rows = self.fetch_many(query).try_filter_map(|step| ready(Ok(step.right())));
+            return Box::pin(async move { rows.try_next().await });
+        }
+        let args = match take_arguments(&mut query) {
+            Ok(a) => a,
             Err(e) => {
-                return Box::pin(async move { Err(sqlx::Error::Encode(e)) });
+                return Box::pin(async move { Err(e) });
             }
         };
-        let rs = match self.0.execute(query.sql(), args.as_slice()).as_sqlx_result() {
+        let sql = self.sql(query.sql());
+        if let Err(e) = check_arguments(&sql, Dialect::Sqlite, args.inner.len()) {
+            return Box::pin(async move { Err(e) });
+        }
+        let rs = match self.inner.execute(&sql, args.as_slice()).as_sqlx_result() {
             Ok(rs) => rs,
             Err(e) => {
                 return Box::pin(async move { Err(e) });
@@ -391,3 +539,92 @@ impl<'c> sqlx::Executor<'c> for &'c Connection {
         todo!("Spin does not currently support sqlx database describe for offline macros")
     }
 }
+
+// Named arguments are put in the order of the SQL's placeholders
+fn take_arguments<'q, E: sqlx::Execute<'q, Connection>>(query: &mut E) -> Result<SpinSqliteArgs, sqlx::Error> {
+    let args = query.take_arguments().map_err(sqlx::Error::Encode)?.unwrap_or_default();
+    args.resolve_named(query.sql())
+}
+
+// A statement's rows, followed by its result
+fn statement_steps(rs: spin_sdk::sqlite::QueryResult) -> Vec<sqlx::Either<SpinSqliteQueryResult, SpinSqliteRow>> {
+    let columns_core = rs.columns.iter().enumerate().map(|(i, c)| SpinSqliteColumn::new(i, c)).collect::<Vec<_>>();
+    let columns = std::sync::Arc::new(columns_core);
+    let rows = rs.rows.into_iter()
+        .map(move |r| sqlx::Either::Right(SpinSqliteRow { columns: columns.clone(), inner: r }));
+    let result = SpinSqliteQueryResult {
+        inner: Some(spin_sdk::sqlite::QueryResult { columns: rs.columns, rows: vec![] }),
+    };
+    rows.chain(std::iter::once(sqlx::Either::Left(result))).collect()
+}
+
+// Generic sqlx code often takes `&mut conn` or `&mut *tx`, so these just pass
+// the query on to the shared reference impl.
+impl<'c> sqlx::Executor<'c> for &'c mut Connection {
+    type Database = Connection;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<
+        'e,
+        Result<
+            sqlx::Either<<Self::Database as sqlx::Database>::QueryResult, <Self::Database as sqlx::Database>::Row>,
+            sqlx::Error,
+        >,
+    >
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>
+    {
+        let conn: &'c Connection = self;
+        conn.fetch_many(query)
+    }
+
+    fn execute<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<<Self::Database as sqlx::Database>::QueryResult, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>,
+    {
+        let conn: &'c Connection = self;
+        conn.execute(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<<Self::Database as sqlx::Database>::Row>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>
+    {
+        let conn: &'c Connection = self;
+        conn.fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [<Self::Database as sqlx::Database>::TypeInfo],
+    ) -> BoxFuture<'e, Result<<Self::Database as sqlx::Database>::Statement<'q>, sqlx::Error>>
+    where
+        'c: 'e
+    {
+        let conn: &'c Connection = self;
+        conn.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<sqlx::Describe<Self::Database>, sqlx::Error>>
+    where
+        'c: 'e
+    {
+        let conn: &'c Connection = self;
+        conn.describe(sql)
+    }
+}
@@ -0,0 +1,317 @@
+use futures_core::stream::BoxStream;
+
+use crate::sql::{placeholders, Dialect, Placeholder};
+
+use super::{Connection, SpinSqliteArgs, SpinSqliteQueryResult, SpinSqliteRow, SpinSqliteStmt};
+
+impl SpinSqliteArgs {
+    /// Adds a value for the SQL's `:name`, `@name` or `$name` parameter.  SQLite
+    /// treats these as three different parameters, so a name given with its
+    /// prefix is the value for that parameter only; a name given without one
+    /// is the value for whichever of them the SQL has, and it's an error for the
+    /// SQL to have more than one.  Binding a name twice is an error.
+    ///
+    /// Arguments can be positional or named, but not both.  When the query runs,
+    /// every named parameter in the SQL must have a value, and every value a
+    /// parameter.
+    pub fn add_named<'q, T>(&mut self, name: &str, value: T) -> Result<(), sqlx::error::BoxDynError>
+    where
+        T: 'q + sqlx::Encode<'q, Connection> + sqlx::Type<Connection>
+    {
+        if let Some((bound, _)) = self.named.iter().find(|(bound, _)| overlap(bound, name)) {
+            return Err(format!("a value is already bound for {bound}, so {name} can't be bound too").into());
+        }
+        let mut buf = vec![];
+        let _ = value.encode_by_ref(&mut buf)?;
+        // Every type encodes as one value, `None` included (as `Null`)
+        let [value] = <[_; 1]>::try_from(buf).map_err(|_| "a named argument must encode as exactly one value")?;
+        self.named.push((name.to_owned(), value));
+        Ok(())
+    }
+
+    // SQLite numbers named parameters in the order they first appear, and Spin
+    // binds values by number, so named values just need putting in that order.
+    pub(super) fn resolve_named(self, sql: &str) -> Result<Self, sqlx::Error> {
+        if self.named.is_empty() {
+            return Ok(self);
+        }
+        if !self.inner.is_empty() {
+            return Err(sqlx::Error::Protocol("can't bind both positional and named arguments to a query".into()));
+        }
+
+        let mut numbered: Vec<Option<&str>> = vec![];
+        let mut seen = vec![];
+        for (_, placeholder) in placeholders(sql, Dialect::Sqlite) {
+            match placeholder {
+                Placeholder::Named(name) if seen.contains(&name) => (),
+                Placeholder::Named(name) => {
+                    seen.push(name);
+                    numbered.push(Some(name));
+                },
+                Placeholder::Next => numbered.push(None),
+                Placeholder::Numbered(n) if n > numbered.len() => numbered.resize(n, None),
+                Placeholder::Numbered(_) => (),
+            }
+        }
+
+        if numbered.iter().any(Option::is_none) {
+            return Err(sqlx::Error::Protocol("can't bind named arguments to SQL with positional parameters".into()));
+        }
+
+        let value_for = |param: &str| self.named.iter().find(|(name, _)| fills(name, param)).map(|(_, value)| value.clone());
+        let missing = seen.iter().filter(|param| value_for(param).is_none()).copied().collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(sqlx::Error::Protocol(format!("no value bound for {}", missing.join(", "))));
+        }
+        for (name, _) in &self.named {
+            let params = seen.iter().filter(|param| fills(name, param)).copied().collect::<Vec<_>>();
+            if params.len() > 1 {
+                return Err(sqlx::Error::Protocol(format!(
+                    "{name} could be any of {}: bind it with its prefix",
+                    params.join(", "),
+                )));
+            }
+        }
+        let extra = self.named.iter()
+            .filter(|(name, _)| !seen.iter().any(|param| fills(name, param)))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        if !extra.is_empty() {
+            return Err(sqlx::Error::Protocol(format!("query has no parameters named {}", extra.join(", "))));
+        }
+
+        let inner = numbered.into_iter().flatten().filter_map(value_for).collect();
+        Ok(Self { inner, named: vec![] })
+    }
+}
+
+const PREFIXES: [char; 3] = [':', '@', '$'];
+
+// Whether a value bound as `name` is the value for the SQL's `param` (which
+// always has its prefix)
+fn fills(name: &str, param: &str) -> bool {
+    if name.starts_with(PREFIXES) { name == param } else { name == &param[1..] }
+}
+
+// Whether values bound as `a` and `b` could fill the same parameter
+fn overlap(a: &str, b: &str) -> bool {
+    match (a.starts_with(PREFIXES), b.starts_with(PREFIXES)) {
+        (true, true) => a == b,
+        (true, false) => fills(b, a),
+        (false, true) => fills(a, b),
+        (false, false) => a == b,
+    }
+}
+
+/// Starts a query whose parameters are named, as `:name`, `@name` or `$name`,
+/// for binding with [`NamedQuery::bind_named`].
+///
+/// ```no_run
+/// # async fn find(conn: &spin_sqlx::sqlite::Connection) -> Result<(), sqlx::Error> {
+/// use spin_sqlx::sqlite::query_named;
+///
+/// let rows = query_named("SELECT * FROM orders WHERE user_id = :user_id AND status = :status")
+///     .bind_named("user_id", 42)
+///     .bind_named("status", "open")
+///     .fetch_all(conn)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn query_named(sql: &str) -> NamedQuery<'_> {
+    NamedQuery { sql, args: SpinSqliteArgs::default(), error: None }
+}
+
+/// A query with named parameters.  See [`query_named`].
+pub struct NamedQuery<'q> {
+    sql: &'q str,
+    args: SpinSqliteArgs,
+    // Reported when the query runs, as `sqlx::Query::bind` does
+    error: Option<sqlx::error::BoxDynError>,
+}
+
+impl<'q> NamedQuery<'q> {
+    /// Binds a value to the named parameter.  See [`SpinSqliteArgs::add_named`].
+    pub fn bind_named<T>(mut self, name: &str, value: T) -> Self
+    where
+        T: 'q + sqlx::Encode<'q, Connection> + sqlx::Type<Connection>
+    {
+        if self.error.is_none() {
+            if let Err(e) = self.args.add_named(name, value) {
+                self.error = Some(e);
+            }
+        }
+        self
+    }
+
+    pub async fn execute<'e, 'c: 'e, E>(self, executor: E) -> Result<SpinSqliteQueryResult, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = Connection>,
+    {
+        executor.execute(self).await
+    }
+
+    pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<SpinSqliteRow, sqlx::Error>>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = Connection>,
+    {
+        executor.fetch(self)
+    }
+
+    pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<SpinSqliteRow>, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = Connection>,
+    {
+        executor.fetch_all(self).await
+    }
+
+    pub async fn fetch_one<'e, 'c: 'e, E>(self, executor: E) -> Result<SpinSqliteRow, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = Connection>,
+    {
+        executor.fetch_one(self).await
+    }
+
+    pub async fn fetch_optional<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<SpinSqliteRow>, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = Connection>,
+    {
+        executor.fetch_optional(self).await
+    }
+}
+
+impl<'q> sqlx::Execute<'q, Connection> for NamedQuery<'q> {
+    fn sql(&self) -> &'q str {
+        self.sql
+    }
+
+    fn statement(&self) -> Option<&SpinSqliteStmt> {
+        None
+    }
+
+    fn take_arguments(&mut self) -> Result<Option<SpinSqliteArgs>, sqlx::error::BoxDynError> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(Some(std::mem::take(&mut self.args))),
+        }
+    }
+
+    fn persistent(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(named: &[(&str, Option<i64>)]) -> SpinSqliteArgs {
+        let mut args = SpinSqliteArgs::default();
+        for (name, value) in named {
+            args.add_named(name, *value).unwrap();
+        }
+        args
+    }
+
+    fn values(args: &SpinSqliteArgs) -> Vec<String> {
+        args.inner.iter().map(|value| format!("{value:?}").replace("Value::", "")).collect()
+    }
+
+    fn error(result: Result<SpinSqliteArgs, sqlx::Error>) -> String {
+        match result {
+            Err(sqlx::Error::Protocol(message)) => message,
+            Err(e) => panic!("expected a protocol error, got {e:?}"),
+            Ok(args) => panic!("expected an error, got {:?}", values(&args)),
+        }
+    }
+
+    #[test]
+    fn named_values_are_put_in_parameter_order() {
+        let resolved = args(&[("b", Some(2)), ("a", Some(1))]).resolve_named("SELECT :a, @b").unwrap();
+        assert_eq!(["Integer(1)", "Integer(2)"], values(&resolved)[..]);
+        assert!(resolved.named.is_empty());
+    }
+
+    #[test]
+    fn repeated_names_are_one_parameter() {
+        let resolved = args(&[("a", Some(1)), ("b", Some(2))]).resolve_named("SELECT :a, :b, :a WHERE x = :b").unwrap();
+        assert_eq!(["Integer(1)", "Integer(2)"], values(&resolved)[..]);
+    }
+
+    #[test]
+    fn names_may_be_given_with_their_prefix() {
+        let resolved = args(&[(":a", Some(1)), ("@b", Some(2)), ("$c", Some(3))]).resolve_named("SELECT $c, :a, @b").unwrap();
+        assert_eq!(["Integer(3)", "Integer(1)", "Integer(2)"], values(&resolved)[..]);
+    }
+
+    #[test]
+    fn none_is_bound_as_null() {
+        let resolved = args(&[("a", None), ("b", Some(2))]).resolve_named("SELECT :a, :b").unwrap();
+        assert_eq!(["Null", "Integer(2)"], values(&resolved)[..]);
+    }
+
+    #[test]
+    fn names_in_strings_and_comments_are_not_parameters() {
+        let resolved = args(&[("a", Some(1))]).resolve_named("SELECT ':b', \":c\" -- :d\n, :a").unwrap();
+        assert_eq!(["Integer(1)"], values(&resolved)[..]);
+    }
+
+    #[test]
+    fn missing_and_extra_values_are_errors() {
+        assert_eq!("no value bound for :b, @c", error(args(&[("a", Some(1))]).resolve_named("SELECT :a, :b, @c, :b")));
+        assert_eq!("query has no parameters named b, c", error(args(&[("a", Some(1)), ("b", Some(2)), ("c", Some(3))]).resolve_named("SELECT :a")));
+        assert_eq!("query has no parameters named @a", error(args(&[(":a", Some(1)), ("@a", Some(2))]).resolve_named("SELECT :a")));
+    }
+
+    #[test]
+    fn prefixes_tell_parameters_apart() {
+        let resolved = args(&[("@a", Some(2)), (":a", Some(1)), ("$a", Some(3))]).resolve_named("SELECT :a, @a, $a, :a").unwrap();
+        assert_eq!(["Integer(1)", "Integer(2)", "Integer(3)"], values(&resolved)[..]);
+
+        assert_eq!("no value bound for @a", error(args(&[(":a", Some(1))]).resolve_named("SELECT :a, @a")));
+        assert_eq!("a could be any of :a, @a: bind it with its prefix", error(args(&[("a", Some(1))]).resolve_named("SELECT :a, @a")));
+
+        // A bare name and a prefixed one can fill different parameters
+        let resolved = args(&[("a", Some(1)), ("@b", Some(2))]).resolve_named("SELECT @a, @b").unwrap();
+        assert_eq!(["Integer(1)", "Integer(2)"], values(&resolved)[..]);
+    }
+
+    #[test]
+    fn names_can_only_be_bound_once() {
+        for (first, second) in [("a", "a"), (":a", ":a"), ("a", ":a"), ("@a", "a")] {
+            let mut args = args(&[(first, Some(1))]);
+            let e = args.add_named(second, Some(2)).unwrap_err();
+            assert_eq!(format!("a value is already bound for {first}, so {second} can't be bound too"), e.to_string());
+            assert_eq!(1, args.named.len());
+        }
+
+        let mut args = args(&[(":a", Some(1))]);
+        args.add_named("@a", Some(2)).unwrap();
+        args.add_named("b", Some(3)).unwrap();
+    }
+
+    #[test]
+    fn named_and_positional_do_not_mix() {
+        for sql in ["SELECT :a, ?", "SELECT ?, :a", "SELECT :a, ?3"] {
+            assert_eq!("can't bind named arguments to SQL with positional parameters", error(args(&[("a", Some(1))]).resolve_named(sql)), "{sql}");
+        }
+        // `?1` is the parameter `:a` is numbered as, so this is fine
+        assert_eq!(["Integer(1)"], values(&args(&[("a", Some(1))]).resolve_named("SELECT :a, ?1").unwrap())[..]);
+
+        let mut both = args(&[("a", Some(1))]);
+        sqlx::Arguments::add(&mut both, 2).unwrap();
+        assert_eq!("can't bind both positional and named arguments to a query", error(both.resolve_named("SELECT :a")));
+    }
+
+    #[test]
+    fn positional_arguments_are_left_alone() {
+        let mut positional = SpinSqliteArgs::default();
+        sqlx::Arguments::add(&mut positional, 1).unwrap();
+        assert_eq!(["Integer(1)"], values(&positional.resolve_named("SELECT :a").unwrap())[..]);
+    }
+}
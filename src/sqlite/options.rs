@@ -0,0 +1,371 @@
+use std::time::Duration;
+
+use futures_core::future::BoxFuture;
+use log::LevelFilter;
+
+use crate::percent;
+
+use super::error::AsSqlxResult;
+use super::Connection;
+
+/// Options for opening a Spin SQLite database.
+///
+/// These can be parsed from a URL such as
+/// `spin-sqlite://label?foreign_keys=on&busy_timeout=5000&journal_mode=wal&synchronous=normal`,
+/// or built up with the setter methods.  The chosen `PRAGMA`s are run as soon as
+/// the database is opened.  `fetch_page_size` and `portable_placeholders`
+/// parameters set [`Connection::fetch_page_size`] and
+/// [`Connection::portable_placeholders`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectionOptions {
+    label: String,
+    foreign_keys: Option<bool>,
+    busy_timeout: Option<Duration>,
+    journal_mode: Option<JournalMode>,
+    synchronous: Option<Synchronous>,
+    fetch_page_size: usize,
+    portable_placeholders: bool,
+}
+
+/// A value for SQLite's `journal_mode` pragma.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+/// A value for SQLite's `synchronous` pragma.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl JournalMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delete => "DELETE",
+            Self::Truncate => "TRUNCATE",
+            Self::Persist => "PERSIST",
+            Self::Memory => "MEMORY",
+            Self::Wal => "WAL",
+            Self::Off => "OFF",
+        }
+    }
+}
+
+impl std::str::FromStr for JournalMode {
+    type Err = sqlx::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "delete" => Ok(Self::Delete),
+            "truncate" => Ok(Self::Truncate),
+            "persist" => Ok(Self::Persist),
+            "memory" => Ok(Self::Memory),
+            "wal" => Ok(Self::Wal),
+            "off" => Ok(Self::Off),
+            _ => Err(config_error(format!("unknown journal_mode '{s}'"))),
+        }
+    }
+}
+
+impl Synchronous {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::Normal => "NORMAL",
+            Self::Full => "FULL",
+            Self::Extra => "EXTRA",
+        }
+    }
+}
+
+impl std::str::FromStr for Synchronous {
+    type Err = sqlx::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "0" => Ok(Self::Off),
+            "normal" | "1" => Ok(Self::Normal),
+            "full" | "2" => Ok(Self::Full),
+            "extra" | "3" => Ok(Self::Extra),
+            _ => Err(config_error(format!("unknown synchronous setting '{s}'"))),
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// Options for opening the database with the given label, running no pragmas.
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_owned(),
+            foreign_keys: None,
+            busy_timeout: None,
+            journal_mode: None,
+            synchronous: None,
+            fetch_page_size: 0,
+            portable_placeholders: false,
+        }
+    }
+
+    /// Reads the database label from the named Spin variable, so that which
+    /// database a component uses can be chosen in `spin.toml` or runtime config.
+    pub fn from_spin_variable(name: &str) -> Result<Self, sqlx::Error> {
+        let label = crate::variables::required(name)?;
+        Ok(Self::new(&label))
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_owned();
+        self
+    }
+
+    /// Sets `PRAGMA foreign_keys`.  SQLite doesn't enforce foreign key
+    /// constraints unless this is turned on.
+    pub fn foreign_keys(mut self, on: bool) -> Self {
+        self.foreign_keys = Some(on);
+        self
+    }
+
+    /// Sets `PRAGMA busy_timeout`: how long to wait for another connection's
+    /// lock before failing with `SQLITE_BUSY`.
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = Some(timeout);
+        self
+    }
+
+    pub fn journal_mode(mut self, mode: JournalMode) -> Self {
+        self.journal_mode = Some(mode);
+        self
+    }
+
+    pub fn synchronous(mut self, synchronous: Synchronous) -> Self {
+        self.synchronous = Some(synchronous);
+        self
+    }
+
+    /// See [`Connection::fetch_page_size`].
+    pub fn fetch_page_size(mut self, size: usize) -> Self {
+        self.fetch_page_size = size;
+        self
+    }
+
+    /// See [`Connection::portable_placeholders`].
+    pub fn portable_placeholders(mut self, portable: bool) -> Self {
+        self.portable_placeholders = portable;
+        self
+    }
+
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    // The busy timeout goes first so that it applies to the other pragmas,
+    // since changing the journal mode needs a lock.
+    fn pragmas(&self) -> Vec<String> {
+        let mut pragmas = vec![];
+        if let Some(timeout) = self.busy_timeout {
+            pragmas.push(format!("PRAGMA busy_timeout = {}", timeout.as_millis()));
+        }
+        if let Some(mode) = self.journal_mode {
+            pragmas.push(format!("PRAGMA journal_mode = {}", mode.as_str()));
+        }
+        if let Some(synchronous) = self.synchronous {
+            pragmas.push(format!("PRAGMA synchronous = {}", synchronous.as_str()));
+        }
+        if let Some(on) = self.foreign_keys {
+            pragmas.push(format!("PRAGMA foreign_keys = {}", if on { "ON" } else { "OFF" }));
+        }
+        pragmas
+    }
+
+    // Spin opens connections synchronously, so this is what `connect` does
+    pub(super) fn open(&self) -> Result<Connection, sqlx::Error> {
+        let conn = spin_sdk::sqlite::Connection::open(&self.label)
+            .map_err(|e| sqlx::Error::AnyDriverError(Box::new(e)))?;
+        for pragma in self.pragmas() {
+            conn.execute(&pragma, &[]).as_sqlx_result()?;
+        }
+        Ok(Connection::new(conn)
+            .fetch_page_size(self.fetch_page_size)
+            .portable_placeholders(self.portable_placeholders))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), sqlx::Error> {
+        match key {
+            "foreign_keys" => self.foreign_keys = Some(parse_bool(key, value)?),
+            "busy_timeout" => {
+                let millis = value.parse().map_err(|_| config_error(format!("invalid busy_timeout '{value}': expected milliseconds")))?;
+                self.busy_timeout = Some(Duration::from_millis(millis));
+            },
+            "journal_mode" => self.journal_mode = Some(value.parse()?),
+            "synchronous" => self.synchronous = Some(value.parse()?),
+            "portable_placeholders" => self.portable_placeholders = parse_bool(key, value)?,
+            "fetch_page_size" => {
+                self.fetch_page_size = value.parse().map_err(|_| config_error(format!("invalid fetch_page_size '{value}': expected a number of rows")))?;
+            },
+            _ => return Err(config_error(format!("unknown connection option '{key}'"))),
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, sqlx::Error> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => Err(config_error(format!("invalid {key} '{value}': expected 'on' or 'off'"))),
+    }
+}
+
+fn config_error(message: String) -> sqlx::Error {
+    sqlx::Error::Configuration(message.into())
+}
+
+impl std::str::FromStr for ConnectionOptions {
+    type Err = sqlx::Error;
+
+    /// Parses either a bare database label, such as `default`, or a
+    /// `spin-sqlite:` URL as described for [`sqlx::ConnectOptions::from_url`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(':') {
+            let url = url::Url::parse(s).map_err(|e| config_error(format!("invalid SQLite URL '{s}': {e}")))?;
+            sqlx::ConnectOptions::from_url(&url)
+        } else {
+            Ok(Self::new(s))
+        }
+    }
+}
+
+impl sqlx::ConnectOptions for ConnectionOptions {
+    type Connection = Connection;
+
+    /// Accepts `spin-sqlite://label` or `spin-sqlite:label`, with pragmas as query
+    /// parameters.  `spin-sqlite://` (or `spin-sqlite:`) means the default database.
+    ///
+    /// ```
+    /// use spin_sqlx::sqlite::ConnectionOptions;
+    ///
+    /// for (url, label) in [
+    ///     ("spin-sqlite://", "default"),
+    ///     ("spin-sqlite:", "default"),
+    ///     ("spin-sqlite://default", "default"),
+    ///     ("spin-sqlite:default", "default"),
+    ///     ("spin-sqlite://orders?foreign_keys=on", "orders"),
+    ///     ("spin-sqlite:orders?journal_mode=wal", "orders"),
+    ///     ("spin-sqlite://a%20b", "a b"),
+    ///     ("spin-sqlite:a%20b", "a b"),
+    ///     ("spin-sqlite://caf%C3%A9?foreign_keys=on", "café"),
+    ///     ("orders", "orders"),
+    /// ] {
+    ///     let options: ConnectionOptions = url.parse().unwrap();
+    ///     assert_eq!(label, options.get_label());
+    /// }
+    ///
+    /// for url in ["spin-pg://localhost/db", "spin-sqlite://orders/extra", "spin-sqlite://user@orders", "spin-sqlite://orders?colour=blue", "spin-sqlite://bad%zz"] {
+    ///     assert!(url.parse::<ConnectionOptions>().is_err());
+    /// }
+    /// ```
+    fn from_url(url: &url::Url) -> Result<Self, sqlx::Error> {
+        match url.scheme() {
+            "spin-sqlite" => (),
+            "spin-pg" => return Err(config_error(format!("'{url}' is a Postgres URL: use `spin_sqlx::pg` to connect to it"))),
+            scheme => return Err(config_error(format!("unsupported URL scheme '{scheme}': expected 'spin-sqlite://label'"))),
+        }
+
+        if !url.username().is_empty() || url.password().is_some() || url.port().is_some() {
+            return Err(config_error(format!("'{url}' has a username, password or port, but Spin SQLite databases are identified only by label")));
+        }
+
+        // `spin-sqlite:label` has no host, just a path
+        let label = if url.cannot_be_a_base() {
+            url.path()
+        } else if url.path().is_empty() || url.path() == "/" {
+            url.host_str().unwrap_or_default()
+        } else {
+            return Err(config_error(format!("unexpected path '{}' in '{url}': expected 'spin-sqlite://label'", url.path())));
+        };
+
+        if label.contains('/') {
+            return Err(config_error(format!("'{label}' is not a valid database label")));
+        }
+        let label = percent::decode(label)?;
+
+        let mut options = Self::new(if label.is_empty() { "default" } else { &label });
+        for (key, value) in url.query_pairs() {
+            options.set(&key, &value)?;
+        }
+        Ok(options)
+    }
+
+    fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, sqlx::Error>>
+    where
+        Self::Connection: Sized
+    {
+        Box::pin(async move { self.open() })
+    }
+
+    fn log_statements(self, _level: LevelFilter) -> Self {
+        self
+    }
+
+    fn log_slow_statements(self, _level: LevelFilter, _duration: std::time::Duration) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pragmas(url: &str) -> Vec<String> {
+        url.parse::<ConnectionOptions>().unwrap().pragmas()
+    }
+
+    #[test]
+    fn pragmas_from_a_url() {
+        assert_eq!(
+            [
+                "PRAGMA busy_timeout = 5000",
+                "PRAGMA journal_mode = WAL",
+                "PRAGMA synchronous = NORMAL",
+                "PRAGMA foreign_keys = ON",
+            ],
+            pragmas("spin-sqlite://orders?foreign_keys=on&synchronous=normal&journal_mode=wal&busy_timeout=5000")[..],
+        );
+        assert_eq!(
+            ["PRAGMA journal_mode = MEMORY", "PRAGMA foreign_keys = OFF"],
+            pragmas("spin-sqlite:orders?foreign_keys=false&journal_mode=MEMORY")[..],
+        );
+    }
+
+    #[test]
+    fn only_pragma_options_make_pragmas() {
+        assert!(pragmas("spin-sqlite://orders").is_empty());
+        assert!(pragmas("spin-sqlite://orders?fetch_page_size=100&portable_placeholders=on").is_empty());
+        assert_eq!(["PRAGMA synchronous = EXTRA"], pragmas("spin-sqlite://orders?synchronous=off&synchronous=extra")[..]);
+    }
+
+    #[test]
+    fn pragmas_from_setters() {
+        let options = ConnectionOptions::new("orders")
+            .foreign_keys(true)
+            .busy_timeout(Duration::from_secs(2))
+            .synchronous(Synchronous::Full);
+        assert_eq!(["PRAGMA busy_timeout = 2000", "PRAGMA synchronous = FULL", "PRAGMA foreign_keys = ON"], options.pragmas()[..]);
+    }
+
+    #[test]
+    fn invalid_pragma_values() {
+        for url in ["spin-sqlite://orders?foreign_keys=maybe", "spin-sqlite://orders?busy_timeout=5s", "spin-sqlite://orders?journal_mode=fast"] {
+            assert!(matches!(url.parse::<ConnectionOptions>(), Err(sqlx::Error::Configuration(_))), "{url}");
+        }
+    }
+}
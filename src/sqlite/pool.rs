@@ -0,0 +1,180 @@
+use std::ops::{Deref, DerefMut};
+
+use futures_core::future::BoxFuture;
+use futures_core::stream::BoxStream;
+
+use crate::pool::{Lease, Pooled, Slot, Slots};
+
+use super::{Connection, ConnectionOptions};
+
+static SLOTS: Slots<ConnectionOptions, Connection> = Slots::new();
+
+impl Pooled for Connection {
+    fn reusable(&self) -> bool {
+        self.transaction_depth == 0
+    }
+}
+
+/// A handle to a Spin SQLite database which opens a connection the first
+/// time it's used, and then reuses it.
+///
+/// Use `&SpinSqlitePool` wherever you'd use `&sqlx::Pool` outside Spin.  Pools
+/// are cheap to create and clone, and all pools with the same options share
+/// one connection, so code need not pass a pool around to avoid opening the
+/// database several times per request.  (Pools with different options, such
+/// as pragmas or page sizes, each have their own.)
+#[derive(Clone, Debug)]
+pub struct SpinSqlitePool {
+    options: ConnectionOptions,
+    conn: Slot<Connection>,
+}
+
+impl SpinSqlitePool {
+    /// A pool for the database with the given label.
+    pub fn new(label: &str) -> Self {
+        Self::with_options(ConnectionOptions::new(label))
+    }
+
+    pub fn with_options(options: ConnectionOptions) -> Self {
+        let conn = SLOTS.get(&options);
+        Self { options, conn }
+    }
+
+    /// The pool's connection, opening it if this is the first use.
+    pub fn connection(&self) -> Result<&Connection, sqlx::Error> {
+        self.conn.shared(|| self.options.open())
+    }
+
+    /// A connection for the caller's exclusive use, for code which needs
+    /// `&mut Connection`, such as `begin()` or functions taking `impl sqlx::Acquire`:
+    /// pass them `&mut *conn`.  This is never the shared connection: it's an idle
+    /// one from an earlier `acquire`, or a newly opened one, and it goes back to
+    /// the pool when dropped.
+    pub async fn acquire(&self) -> Result<SpinSqlitePoolConnection, sqlx::Error> {
+        self.conn.lease(|| self.options.open()).map(SpinSqlitePoolConnection)
+    }
+}
+
+/// A connection taken from a pool by [`SpinSqlitePool::acquire`].
+#[derive(Debug)]
+pub struct SpinSqlitePoolConnection(Lease<Connection>);
+
+impl Deref for SpinSqlitePoolConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.0
+    }
+}
+
+impl DerefMut for SpinSqlitePoolConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.0
+    }
+}
+
+impl Default for SpinSqlitePool {
+    /// A pool for the `default` database.
+    fn default() -> Self {
+        Self::new("default")
+    }
+}
+
+impl<'c> sqlx::Executor<'c> for &'c SpinSqlitePool {
+    type Database = Connection;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<
+        'e,
+        Result<
+            sqlx::Either<<Self::Database as sqlx::Database>::QueryResult, <Self::Database as sqlx::Database>::Row>,
+            sqlx::Error,
+        >,
+    >
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>
+    {
+        match self.connection() {
+            Ok(conn) => conn.fetch_many(query),
+            Err(e) => Box::pin(futures::stream::once(async move { Err(e) })),
+        }
+    }
+
+    fn execute<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<<Self::Database as sqlx::Database>::QueryResult, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>,
+    {
+        match self.connection() {
+            Ok(conn) => conn.execute(query),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<<Self::Database as sqlx::Database>::Row>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + sqlx::Execute<'q, Self::Database>
+    {
+        match self.connection() {
+            Ok(conn) => conn.fetch_optional(query),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [<Self::Database as sqlx::Database>::TypeInfo],
+    ) -> BoxFuture<'e, Result<<Self::Database as sqlx::Database>::Statement<'q>, sqlx::Error>>
+    where
+        'c: 'e
+    {
+        match self.connection() {
+            Ok(conn) => conn.prepare_with(sql, parameters),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<sqlx::Describe<Self::Database>, sqlx::Error>>
+    where
+        'c: 'e
+    {
+        match self.connection() {
+            Ok(conn) => conn.describe(sql),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn pools_share_a_connection_only_with_the_same_options() {
+        let pool = SpinSqlitePool::new("pool-test");
+        assert!(Arc::ptr_eq(&pool.conn, &SpinSqlitePool::new("pool-test").conn));
+        assert!(Arc::ptr_eq(&pool.conn, &pool.clone().conn));
+        assert!(!Arc::ptr_eq(&pool.conn, &SpinSqlitePool::new("pool-test-2").conn));
+
+        let with_pragmas = |options: ConnectionOptions| SpinSqlitePool::with_options(options.foreign_keys(true)).conn;
+        assert!(!Arc::ptr_eq(&pool.conn, &with_pragmas(ConnectionOptions::new("pool-test"))));
+        assert!(Arc::ptr_eq(&with_pragmas(ConnectionOptions::new("pool-test")), &with_pragmas(ConnectionOptions::new("pool-test"))));
+        assert!(!Arc::ptr_eq(&pool.conn, &SpinSqlitePool::with_options(ConnectionOptions::new("pool-test").fetch_page_size(10)).conn));
+    }
+}
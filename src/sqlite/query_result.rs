@@ -48,6 +48,51 @@ impl sqlx::Row for SpinSqliteRow {
     }
 }
 
+impl SpinSqliteRow {
+    /// The row as Spin returned it.
+    pub fn as_inner(&self) -> &spin_sdk::sqlite::RowResult {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> spin_sdk::sqlite::RowResult {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+impl SpinSqliteRow {
+    /// A row of the given columns and values, for tests.
+    pub(crate) fn for_test(values: Vec<(&str, spin_sdk::sqlite::Value)>) -> Self {
+        let columns = values.iter().enumerate().map(|(i, (name, _))| SpinSqliteColumn::new(i, name)).collect();
+        let values = values.into_iter().map(|(_, value)| value).collect();
+        Self { columns: std::sync::Arc::new(columns), inner: spin_sdk::sqlite::RowResult { values } }
+    }
+}
+
+impl From<SpinSqliteRow> for spin_sdk::sqlite::RowResult {
+    fn from(row: SpinSqliteRow) -> Self {
+        row.inner
+    }
+}
+
+impl SpinSqliteQueryResult {
+    /// The result as Spin returned it, if a statement ran.  Results yielded by
+    /// `fetch_many` have no rows, since those were yielded separately.
+    pub fn as_inner(&self) -> Option<&spin_sdk::sqlite::QueryResult> {
+        self.inner.as_ref()
+    }
+
+    pub fn into_inner(self) -> Option<spin_sdk::sqlite::QueryResult> {
+        self.inner
+    }
+}
+
+impl From<spin_sdk::sqlite::QueryResult> for SpinSqliteQueryResult {
+    fn from(result: spin_sdk::sqlite::QueryResult) -> Self {
+        Self { inner: Some(result) }
+    }
+}
+
 impl Extend<SpinSqliteQueryResult> for SpinSqliteQueryResult {
     fn extend<T: IntoIterator<Item = SpinSqliteQueryResult>>(&mut self, iter: T) {
         for mut qr in iter.into_iter() {
@@ -72,8 +117,9 @@ impl sqlx::Column for SpinSqliteColumn {
         &self.name
     }
 
+    // Spin doesn't tell us columns' declared types, only the types of values
     fn type_info(&self) -> &<Self::Database as sqlx::Database>::TypeInfo {
-        todo!()
+        &SpinSqliteTypeInfo::Null
     }
 }
 
@@ -82,6 +128,28 @@ pub struct SpinSqliteValue {
     pub(crate) inner: spin_sdk::sqlite::Value,
 }
 
+impl SpinSqliteValue {
+    pub fn as_inner(&self) -> &spin_sdk::sqlite::Value {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> spin_sdk::sqlite::Value {
+        self.inner
+    }
+}
+
+impl From<spin_sdk::sqlite::Value> for SpinSqliteValue {
+    fn from(inner: spin_sdk::sqlite::Value) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<SpinSqliteValue> for spin_sdk::sqlite::Value {
+    fn from(value: SpinSqliteValue) -> Self {
+        value.inner
+    }
+}
+
 impl<'q> sqlx::ValueRef<'q> for SpinSqliteValue {
     type Database = Connection;
 
@@ -109,15 +177,15 @@ impl sqlx::Value for SpinSqliteValue {
     type Database = Connection;
 
     fn as_ref(&self) -> <Self::Database as sqlx::Database>::ValueRef<'_> {
-        todo!()
+        self.clone()
     }
 
     fn type_info(&self) -> std::borrow::Cow<'_, <Self::Database as sqlx::Database>::TypeInfo> {
-        todo!()
+        sqlx::ValueRef::type_info(self)
     }
 
     fn is_null(&self) -> bool {
-        todo!()
+        sqlx::ValueRef::is_null(self)
     }
 }
 
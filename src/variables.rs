@@ -0,0 +1,26 @@
+use spin_sdk::variables::{self, Error};
+
+// Spin variables are declared in the component manifest and set in runtime
+// config, so a bad or missing one is a configuration problem rather than a
+// database one.
+
+pub(crate) fn optional(name: &str) -> Result<Option<String>, sqlx::Error> {
+    match variables::get(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::Undefined(_)) => Ok(None),
+        Err(e) => Err(variable_error(name, e)),
+    }
+}
+
+pub(crate) fn required(name: &str) -> Result<String, sqlx::Error> {
+    variables::get(name).map_err(|e| variable_error(name, e))
+}
+
+fn variable_error(name: &str, e: Error) -> sqlx::Error {
+    let message = match e {
+        Error::InvalidName(_) => format!("'{name}' is not a valid Spin variable name: names may contain only lower-case letters, digits and underscores"),
+        Error::Undefined(_) => format!("Spin variable '{name}' is not defined: check the component's `[component.<name>.variables]` in spin.toml"),
+        Error::Provider(m) | Error::Other(m) => format!("error reading Spin variable '{name}': {m}"),
+    };
+    sqlx::Error::Configuration(message.into())
+}